@@ -0,0 +1,39 @@
+// Times the label pass: `cargo bench --bench labels`
+use gushy::font::{Fonts, GlyphCache, REGULAR, TextAlign, TextStyle, draw_text};
+use std::time::Instant;
+use tiny_skia::{Color, Pixmap};
+
+const FRAMES: u32 = 20;
+
+fn main() {
+    let fonts = Fonts::bundled();
+    let mut cache = GlyphCache::default();
+    let mut pixmap = Pixmap::new(1920, 1080).unwrap();
+    let style = TextStyle::new(REGULAR, 14.0, TextAlign::Center)
+        .with_color(Color::from_rgba8(20, 20, 20, 230));
+
+    for labels in [100, 1_000, 5_000, 10_000] {
+        // Spread the labels over the frame the way dots would be
+        let positions: Vec<(f64, f64)> = (0..labels)
+            .map(|i| {
+                let t = i as f64 * 0.618_033_988_7;
+                (t.fract() * 1920.0, (i as f64 / labels as f64) * 1080.0)
+            })
+            .collect();
+
+        let start = Instant::now();
+        for _ in 0..FRAMES {
+            pixmap.fill(Color::WHITE);
+            for (i, &(x, y)) in positions.iter().enumerate() {
+                let label = format!("file_{i}.rs");
+                draw_text(&mut pixmap, &mut cache, &fonts, &label, x, y, style);
+            }
+        }
+        let per_frame = start.elapsed() / FRAMES;
+        println!(
+            "{labels:>6} labels: {:>8.2} ms/frame ({:.2} us/label)",
+            per_frame.as_secs_f64() * 1000.0,
+            per_frame.as_secs_f64() * 1e6 / labels as f64
+        );
+    }
+}
@@ -0,0 +1,29 @@
+// Times update_dots: `cargo bench --bench step`, and again with
+// `--features parallel` to compare
+use gushy::integrate::Integrator;
+use gushy::{State, update_dots};
+use std::time::Instant;
+
+const STEPS: u32 = 20;
+
+fn main() {
+    for integrator in [Integrator::SemiImplicitEuler, Integrator::Rk4] {
+        for dots in [500, 1_000, 2_000] {
+            let mut state = State::with_seed(dots, 1920, 1080, 1);
+            state.integrator = integrator;
+            update_dots(&mut state); // Sizes the grid and buffers
+
+            let start = Instant::now();
+            for _ in 0..STEPS {
+                update_dots(&mut state);
+            }
+            let per_step = start.elapsed() / STEPS;
+            println!(
+                "{:>20} {dots:>6} dots: {:>8.2} ms/step (fingerprint {:016x})",
+                integrator.name(),
+                per_step.as_secs_f64() * 1000.0,
+                state.fingerprint()
+            );
+        }
+    }
+}
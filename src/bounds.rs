@@ -0,0 +1,168 @@
+use crate::math::Pair;
+use serde::{Deserialize, Serialize};
+
+pub const DEFAULT_MARGIN: f32 = 40.0;
+pub const DEFAULT_DAMPING: f32 = 0.85;
+
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum EdgeBehavior {
+    Reflect, // Bounce back, losing some speed
+    Wrap,    // Reappear at the opposite edge
+    Absorb,  // Delete the dot
+    Open,    // Let it leave
+}
+
+impl EdgeBehavior {
+    pub fn next(self) -> EdgeBehavior {
+        match self {
+            EdgeBehavior::Reflect => EdgeBehavior::Wrap,
+            EdgeBehavior::Wrap => EdgeBehavior::Absorb,
+            EdgeBehavior::Absorb => EdgeBehavior::Open,
+            EdgeBehavior::Open => EdgeBehavior::Reflect,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum BoundsArea {
+    Window { margin: f32 },         // Window rect shrunk by margin on every side
+    World { min: Pair, max: Pair }, // Fixed rect in world coordinates
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct Bounds {
+    pub area: BoundsArea,
+    pub left: EdgeBehavior,
+    pub right: EdgeBehavior,
+    pub top: EdgeBehavior,
+    pub bottom: EdgeBehavior,
+    pub damping: f32,
+}
+
+impl Default for Bounds {
+    fn default() -> Self {
+        Bounds::window(DEFAULT_MARGIN)
+    }
+}
+
+impl Bounds {
+    pub fn window(margin: f32) -> Self {
+        Bounds {
+            area: BoundsArea::Window { margin },
+            left: EdgeBehavior::Reflect,
+            right: EdgeBehavior::Reflect,
+            top: EdgeBehavior::Reflect,
+            bottom: EdgeBehavior::Reflect,
+            damping: DEFAULT_DAMPING,
+        }
+    }
+
+    pub fn world(min: Pair, max: Pair) -> Self {
+        Bounds {
+            area: BoundsArea::World { min, max },
+            ..Bounds::window(0.0)
+        }
+    }
+
+    pub fn with_edges(mut self, behavior: EdgeBehavior) -> Self {
+        self.set_edges(behavior);
+        self
+    }
+
+    pub fn set_edges(&mut self, behavior: EdgeBehavior) {
+        self.left = behavior;
+        self.right = behavior;
+        self.top = behavior;
+        self.bottom = behavior;
+    }
+
+    // World-space rect as (min, max) for a window of `size` logical pixels;
+    // the world origin sits at the window center.
+    pub fn rect(&self, size: Pair) -> (Pair, Pair) {
+        match self.area {
+            BoundsArea::Window { margin } => {
+                let half = Pair::new(
+                    (size.x / 2.0 - margin).max(0.0),
+                    (size.y / 2.0 - margin).max(0.0),
+                );
+                (-half, half)
+            }
+            BoundsArea::World { min, max } => (min, max),
+        }
+    }
+
+    // Applies the edge rules to one dot. Returns false if the dot was absorbed.
+    pub fn apply(
+        &self,
+        position: &mut Pair,
+        previous_position: &mut Pair,
+        velocity: &mut Pair,
+        (min, max): (Pair, Pair),
+    ) -> bool {
+        let size = max - min;
+
+        if position.x >= max.x {
+            match self.right {
+                EdgeBehavior::Reflect => {
+                    position.x = max.x - 0.5; // Move slightly away
+                    self.bounce_x(velocity);
+                }
+                EdgeBehavior::Wrap => wrap(position, previous_position, Pair::new(-size.x, 0.0)),
+                EdgeBehavior::Absorb => return false,
+                EdgeBehavior::Open => {}
+            }
+        } else if position.x <= min.x {
+            match self.left {
+                EdgeBehavior::Reflect => {
+                    position.x = min.x + 0.5;
+                    self.bounce_x(velocity);
+                }
+                EdgeBehavior::Wrap => wrap(position, previous_position, Pair::new(size.x, 0.0)),
+                EdgeBehavior::Absorb => return false,
+                EdgeBehavior::Open => {}
+            }
+        }
+
+        if position.y >= max.y {
+            match self.bottom {
+                EdgeBehavior::Reflect => {
+                    position.y = max.y - 0.5;
+                    self.bounce_y(velocity);
+                }
+                EdgeBehavior::Wrap => wrap(position, previous_position, Pair::new(0.0, -size.y)),
+                EdgeBehavior::Absorb => return false,
+                EdgeBehavior::Open => {}
+            }
+        } else if position.y <= min.y {
+            match self.top {
+                EdgeBehavior::Reflect => {
+                    position.y = min.y + 0.5;
+                    self.bounce_y(velocity);
+                }
+                EdgeBehavior::Wrap => wrap(position, previous_position, Pair::new(0.0, size.y)),
+                EdgeBehavior::Absorb => return false,
+                EdgeBehavior::Open => {}
+            }
+        }
+
+        true
+    }
+
+    fn bounce_x(&self, velocity: &mut Pair) {
+        velocity.x = -velocity.x * self.damping;
+        velocity.y *= self.damping; // Reduce y velocity to avoid getting stuck in corners
+    }
+
+    fn bounce_y(&self, velocity: &mut Pair) {
+        velocity.y = -velocity.y * self.damping;
+        velocity.x *= self.damping; // Reduce x velocity to avoid getting stuck in corners
+    }
+}
+
+// Shift the previous position too so render interpolation doesn't streak across the screen
+fn wrap(position: &mut Pair, previous_position: &mut Pair, offset: Pair) {
+    *position += offset;
+    *previous_position += offset;
+}
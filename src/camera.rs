@@ -0,0 +1,58 @@
+use crate::math::Pair;
+use serde::{Deserialize, Serialize};
+use tiny_skia::Transform;
+
+pub const MIN_CAMERA_SCALE: f32 = 0.05;
+pub const MAX_CAMERA_SCALE: f32 = 50.0;
+
+// Maps world units to screen pixels. `viewport` is the target size in physical
+// pixels and `pixel_ratio` the HiDPI scale factor, so at scale 1 one world unit
+// is one logical pixel and the camera center sits in the middle of the screen.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct Camera {
+    pub center: Pair,  // World position at the middle of the screen
+    pub scale: f32,    // Logical pixels per world unit
+    pub rotation: f32, // Radians, applied around the center
+}
+
+impl Default for Camera {
+    fn default() -> Self {
+        Camera {
+            center: Pair::new(0.0, 0.0),
+            scale: 1.0,
+            rotation: 0.0,
+        }
+    }
+}
+
+impl Camera {
+    pub fn transform(&self, viewport: Pair, pixel_ratio: f32) -> Transform {
+        let scale = self.scale * pixel_ratio;
+        Transform::from_translate(-self.center.x, -self.center.y)
+            .post_rotate(self.rotation.to_degrees())
+            .post_scale(scale, scale)
+            .post_translate(viewport.x / 2.0, viewport.y / 2.0)
+    }
+
+    pub fn world_to_screen(&self, world: Pair, viewport: Pair, pixel_ratio: f32) -> Pair {
+        (world - self.center).rotate(self.rotation) * (self.scale * pixel_ratio) + viewport / 2.0
+    }
+
+    pub fn screen_to_world(&self, screen: Pair, viewport: Pair, pixel_ratio: f32) -> Pair {
+        ((screen - viewport / 2.0) / (self.scale * pixel_ratio)).rotate(-self.rotation)
+            + self.center
+    }
+
+    // Zooms by `factor` while keeping the world point under `screen` fixed
+    pub fn zoom_at(&mut self, screen: Pair, factor: f32, viewport: Pair, pixel_ratio: f32) {
+        let anchor = self.screen_to_world(screen, viewport, pixel_ratio);
+        self.scale = (self.scale * factor).clamp(MIN_CAMERA_SCALE, MAX_CAMERA_SCALE);
+        let moved = self.screen_to_world(screen, viewport, pixel_ratio);
+        self.center += anchor - moved;
+    }
+
+    // Drags the view so the world follows the cursor by `screen_delta` pixels
+    pub fn pan_by(&mut self, screen_delta: Pair, pixel_ratio: f32) {
+        self.center -= (screen_delta / (self.scale * pixel_ratio)).rotate(-self.rotation);
+    }
+}
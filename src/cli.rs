@@ -0,0 +1,190 @@
+use crate::integrate::Integrator;
+use crate::scene::{Scene, SceneError, WindowSettings};
+use crate::{MAX_SEED, State, random_seed};
+use crate::{snapshot, tree};
+use std::path::PathBuf;
+
+pub const USAGE: &str = "\
+Usage: gushy [OPTIONS]
+
+Options:
+  --dots <N>          Number of dots to spawn (default 30)
+  --size <WxH>        Window size in logical pixels or image size (default 800x600, or the scene's)
+  --path <DIR>        Show the files under DIR instead of random dots
+  --scene <FILE>      Load dots and settings from a TOML scene file
+  --seed <N>          Seed for everything random, for repeatable runs
+  --integrator <NAME> semi-implicit-euler (default), velocity-verlet or rk4
+  --snapshot <FILE>   Start from a saved snapshot
+  --record <FILE>     Record all input to a file when the window closes
+  --replay <FILE>     Replay a recorded session instead of taking input
+  --save-snapshot <FILE>
+                      Where headless mode saves a snapshot after its steps
+  --headless          Run without a window and write a PNG
+  --steps <N>         Simulation steps to run in headless mode (default 600)
+  --output <PATH>     Where headless mode writes its image (default gushy.png)
+  --print-debug       Also print debug info to the terminal every frame
+  -h, --help          Print this message";
+
+#[derive(Debug, Clone)]
+pub struct Args {
+    pub dots: usize,
+    pub size: Option<(u32, u32)>, // Overrides the scene's window size
+    pub path: Option<PathBuf>,
+    pub scene: Option<PathBuf>,
+    pub seed: Option<u64>,              // Overrides the scene's seed
+    pub integrator: Option<Integrator>, // Overrides the scene's integrator
+    pub snapshot: Option<PathBuf>,
+    pub save_snapshot: Option<PathBuf>,
+    pub record: Option<PathBuf>,
+    pub replay: Option<PathBuf>, // With --headless, runs to the end of the recording
+    pub headless: bool,
+    pub steps: u64,
+    pub output: PathBuf,
+    pub print_debug: bool, // Terminal printer on top of the in-window HUD
+    pub help: bool,
+}
+
+impl Default for Args {
+    fn default() -> Self {
+        Args {
+            dots: 30,
+            size: None,
+            path: None,
+            scene: None,
+            seed: None,
+            integrator: None,
+            snapshot: None,
+            save_snapshot: None,
+            record: None,
+            replay: None,
+            headless: false,
+            steps: 600,
+            output: PathBuf::from("gushy.png"),
+            print_debug: false,
+            help: false,
+        }
+    }
+}
+
+impl Args {
+    pub fn load_scene(&self) -> Result<Option<Scene>, SceneError> {
+        self.scene.as_deref().map(Scene::load).transpose()
+    }
+
+    // Window size in logical pixels, or the image size in headless mode
+    pub fn size(&self, scene: Option<&Scene>) -> (u32, u32) {
+        self.size.unwrap_or_else(|| {
+            let window = scene.map_or_else(WindowSettings::default, |scene| scene.window.clone());
+            (window.width, window.height)
+        })
+    }
+
+    // Builds the initial state these options describe. A scene's dots give way
+    // to --path, but its settings still apply; a snapshot replaces both.
+    pub fn build_state(
+        &self,
+        scene: Option<&Scene>,
+        width: u32,
+        height: u32,
+    ) -> Result<State, String> {
+        let seed = self
+            .seed
+            .or(scene.and_then(|scene| scene.sim.seed))
+            .unwrap_or_else(random_seed);
+        let mut state = match (&self.path, scene) {
+            (Some(path), _) => {
+                let mut state = State::with_seed(0, width, height, seed);
+                tree::populate(&mut state, path)
+                    .map_err(|err| format!("Can't read {}: {err}", path.display()))?;
+                if let Some(scene) = scene {
+                    scene.apply_sim(&mut state);
+                }
+                state
+            }
+            (None, Some(scene)) => scene
+                .build_state(width, height, seed)
+                .map_err(|err| err.to_string())?,
+            (None, None) => State::with_seed(self.dots, width, height, seed),
+        };
+        if let Some(path) = &self.snapshot {
+            snapshot::load(&mut state, path).map_err(|err| err.to_string())?;
+        }
+        // After the snapshot, which brings its own
+        if let Some(integrator) = self.integrator {
+            state.integrator = integrator;
+            state.momentum.reset();
+        }
+        Ok(state)
+    }
+
+    // Parses everything after the program name
+    pub fn parse<I: IntoIterator<Item = String>>(args: I) -> Result<Args, String> {
+        let mut parsed = Args::default();
+        let mut args = args.into_iter();
+
+        while let Some(arg) = args.next() {
+            match arg.as_str() {
+                "--dots" => parsed.dots = parse_value(&arg, args.next())?,
+                "--size" => {
+                    let value = required(&arg, args.next())?;
+                    let (width, height) = value
+                        .split_once('x')
+                        .and_then(|(w, h)| Some((w.parse().ok()?, h.parse().ok()?)))
+                        .filter(|&(w, h): &(u32, u32)| w > 0 && h > 0)
+                        .ok_or_else(|| format!("Invalid size '{value}', expected WxH"))?;
+                    parsed.size = Some((width, height));
+                }
+                "--path" => parsed.path = Some(PathBuf::from(required(&arg, args.next())?)),
+                "--scene" => parsed.scene = Some(PathBuf::from(required(&arg, args.next())?)),
+                "--seed" => {
+                    let seed = parse_value(&arg, args.next())?;
+                    if seed > MAX_SEED {
+                        return Err(format!("--seed can be at most {MAX_SEED}"));
+                    }
+                    parsed.seed = Some(seed);
+                }
+                "--integrator" => {
+                    let value = required(&arg, args.next())?;
+                    let integrator = Integrator::from_name(&value).ok_or_else(|| {
+                        let names: Vec<_> = Integrator::ALL.iter().map(|i| i.name()).collect();
+                        format!(
+                            "Unknown integrator '{value}', expected {}",
+                            names.join(", ")
+                        )
+                    })?;
+                    parsed.integrator = Some(integrator);
+                }
+                "--snapshot" => parsed.snapshot = Some(PathBuf::from(required(&arg, args.next())?)),
+                "--save-snapshot" => {
+                    parsed.save_snapshot = Some(PathBuf::from(required(&arg, args.next())?))
+                }
+                "--record" => parsed.record = Some(PathBuf::from(required(&arg, args.next())?)),
+                "--replay" => parsed.replay = Some(PathBuf::from(required(&arg, args.next())?)),
+                "--headless" => parsed.headless = true,
+                "--steps" => parsed.steps = parse_value(&arg, args.next())?,
+                "--output" => parsed.output = PathBuf::from(required(&arg, args.next())?),
+                "--print-debug" => parsed.print_debug = true,
+                "-h" | "--help" => parsed.help = true,
+                _ => return Err(format!("Unknown argument '{arg}'")),
+            }
+        }
+
+        if parsed.record.is_some() && (parsed.headless || parsed.replay.is_some()) {
+            return Err(String::from(
+                "--record needs live input, so can't go with --headless or --replay",
+            ));
+        }
+        Ok(parsed)
+    }
+}
+
+fn required(flag: &str, value: Option<String>) -> Result<String, String> {
+    value.ok_or_else(|| format!("{flag} needs a value"))
+}
+
+fn parse_value<T: std::str::FromStr>(flag: &str, value: Option<String>) -> Result<T, String> {
+    let value = required(flag, value)?;
+    value
+        .parse()
+        .map_err(|_| format!("Invalid value '{value}' for {flag}"))
+}
@@ -35,9 +35,10 @@ pub fn print_debug(state: &mut State) {
     std::io::stdout().flush().unwrap(); // Ensure output is written immediately
 }
 
+// FPS over the profiler's recent frames, and seconds since start
 fn calculate_fps(state: &State) -> (f32, f32) {
     let elapsed = state.time_info.start.elapsed().as_secs_f32();
-    let fps = state.time_info.frame_count as f32 / elapsed;
+    let fps = state.profiler.fps();
 
     (fps, elapsed)
 }
@@ -0,0 +1,138 @@
+use crate::replay::RECORDING_VERSION;
+use crate::snapshot::SNAPSHOT_VERSION;
+use serde::de::DeserializeOwned;
+use serde::{Deserialize, Serialize};
+use std::fmt;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+
+// The versioned TOML files gushy writes
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FileKind {
+    Snapshot,
+    Recording,
+}
+
+impl FileKind {
+    pub fn name(self) -> &'static str {
+        match self {
+            FileKind::Snapshot => "snapshot",
+            FileKind::Recording => "recording",
+        }
+    }
+
+    // The version written now. Bumped whenever that file's layout changes;
+    // older files still load, newer ones are refused.
+    pub fn version(self) -> u32 {
+        match self {
+            FileKind::Snapshot => SNAPSHOT_VERSION,
+            FileKind::Recording => RECORDING_VERSION,
+        }
+    }
+}
+
+impl fmt::Display for FileKind {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.name())
+    }
+}
+
+#[derive(Debug)]
+pub enum FileError {
+    Io {
+        kind: FileKind,
+        path: PathBuf,
+        source: io::Error,
+    },
+    Parse {
+        kind: FileKind,
+        message: String,
+    },
+    Serialize {
+        kind: FileKind,
+        message: String,
+    },
+    Version {
+        kind: FileKind,
+        version: u32, // Written by a newer build
+    },
+}
+
+impl fmt::Display for FileError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            FileError::Io { kind, path, source } => {
+                write!(f, "Can't access {kind} {}: {source}", path.display())
+            }
+            FileError::Parse { kind, message } => write!(f, "Invalid {kind}: {message}"),
+            FileError::Serialize { kind, message } => write!(f, "Can't write {kind}: {message}"),
+            FileError::Version { kind, version } => write!(
+                f,
+                "Can't read {kind} version {version}; this build supports up to {}",
+                kind.version()
+            ),
+        }
+    }
+}
+
+impl std::error::Error for FileError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            FileError::Io { source, .. } => Some(source),
+            _ => None,
+        }
+    }
+}
+
+// Refuses `text` if its version is newer than this build writes for `kind`.
+// Checked on its own, so a newer file gets a clear error rather than
+// whatever field it added.
+pub fn check_version(kind: FileKind, text: &str) -> Result<(), FileError> {
+    #[derive(Deserialize)]
+    struct Header {
+        version: u32,
+    }
+    let header: Header = toml::from_str(text).map_err(|err| FileError::Parse {
+        kind,
+        message: err.to_string(),
+    })?;
+    if header.version > kind.version() {
+        return Err(FileError::Version {
+            kind,
+            version: header.version,
+        });
+    }
+    Ok(())
+}
+
+pub fn from_toml<T: DeserializeOwned>(kind: FileKind, text: &str) -> Result<T, FileError> {
+    check_version(kind, text)?;
+    toml::from_str(text).map_err(|err| FileError::Parse {
+        kind,
+        message: err.to_string(),
+    })
+}
+
+pub fn to_toml(kind: FileKind, value: &impl Serialize) -> Result<String, FileError> {
+    toml::to_string(value).map_err(|err| FileError::Serialize {
+        kind,
+        message: err.to_string(),
+    })
+}
+
+pub fn read(kind: FileKind, path: &Path) -> Result<String, FileError> {
+    fs::read_to_string(path).map_err(|source| FileError::Io {
+        kind,
+        path: path.to_path_buf(),
+        source,
+    })
+}
+
+pub fn write(kind: FileKind, path: &Path, text: &str) -> Result<(), FileError> {
+    fs::write(path, text).map_err(|source| FileError::Io {
+        kind,
+        path: path.to_path_buf(),
+        source,
+    })
+}
@@ -1,118 +1,306 @@
-use crate::State;
-use fontdue::{Font, Metrics};
+use crate::layout::{LayoutOptions, TextBounds, draw_layout, layout_text};
+use fontdue::{Font, FontSettings, Metrics};
 use std::collections::HashMap;
-use tiny_skia::{Color, IntRect, Paint, Pixmap, Transform};
+use tiny_skia::{Color, Pixmap};
+
+pub const DEFAULT_GLYPH_CACHE_BYTES: usize = 4 * 1024 * 1024;
+const SIZE_STEPS_PER_PIXEL: f32 = 4.0; // Glyph sizes are cached in quarter pixels
 
 pub struct CachedGlyph {
     pub metrics: Metrics,
     pub bitmap: Vec<u8>,
 }
 
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct FontId(pub usize);
+
+pub const REGULAR: FontId = FontId(0);
+pub const BOLD: FontId = FontId(1);
+
+// Every font text can be drawn with, addressed by FontId
+pub struct Fonts {
+    fonts: Vec<Font>,
+}
+
+impl Fonts {
+    pub fn new() -> Self {
+        Fonts { fonts: Vec::new() }
+    }
+
+    // The fonts shipped in fonts/, at REGULAR and BOLD
+    pub fn bundled() -> Self {
+        let mut fonts = Fonts::new();
+        fonts
+            .load(include_bytes!("../fonts/LTInternet-Regular.ttf"))
+            .expect("Bundled regular font is valid");
+        fonts
+            .load(include_bytes!("../fonts/LTInternet-Bold.ttf"))
+            .expect("Bundled bold font is valid");
+        fonts
+    }
+
+    pub fn add(&mut self, font: Font) -> FontId {
+        self.fonts.push(font);
+        FontId(self.fonts.len() - 1)
+    }
+
+    pub fn load(&mut self, data: &[u8]) -> Result<FontId, &'static str> {
+        let font = Font::from_bytes(data, FontSettings::default())?;
+        Ok(self.add(font))
+    }
+
+    // Unknown ids fall back to the first font
+    pub fn get(&self, id: FontId) -> &Font {
+        self.fonts
+            .get(id.0)
+            .or(self.fonts.first())
+            .expect("At least one font is loaded")
+    }
+
+    pub fn len(&self) -> usize {
+        self.fonts.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.fonts.is_empty()
+    }
+}
+
+impl Default for Fonts {
+    fn default() -> Self {
+        Fonts::new()
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct GlyphKey {
+    pub font: FontId,
+    pub c: char,
+    pub size: u32, // Quarter pixels, see quantize_size
+}
+
+impl GlyphKey {
+    pub fn new(font: FontId, c: char, font_size: f64) -> Self {
+        GlyphKey {
+            font,
+            c,
+            size: quantize_size(font_size),
+        }
+    }
+
+    pub fn px(&self) -> f32 {
+        self.size as f32 / SIZE_STEPS_PER_PIXEL
+    }
+}
+
+pub fn quantize_size(font_size: f64) -> u32 {
+    (font_size.max(0.0) as f32 * SIZE_STEPS_PER_PIXEL).round() as u32
+}
+
+// Rasterized glyphs, dropping the least recently used ones once their bitmaps
+// take up more than `capacity_bytes`
+pub struct GlyphCache {
+    entries: HashMap<GlyphKey, (CachedGlyph, u64)>,
+    bytes: usize,
+    capacity_bytes: usize,
+    clock: u64,
+}
+
+impl GlyphCache {
+    pub fn new(capacity_bytes: usize) -> Self {
+        GlyphCache {
+            entries: HashMap::new(),
+            bytes: 0,
+            capacity_bytes,
+            clock: 0,
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+
+    pub fn bytes(&self) -> usize {
+        self.bytes
+    }
+
+    pub fn clear(&mut self) {
+        self.entries.clear();
+        self.bytes = 0;
+    }
+
+    pub fn get(&mut self, fonts: &Fonts, key: GlyphKey) -> &CachedGlyph {
+        self.clock += 1;
+        if !self.entries.contains_key(&key) {
+            let (metrics, bitmap) = fonts.get(key.font).rasterize(key.c, key.px());
+            self.bytes += bitmap.len();
+            self.entries
+                .insert(key, (CachedGlyph { metrics, bitmap }, self.clock));
+            self.evict(key);
+        }
+
+        let (glyph, last_used) = self.entries.get_mut(&key).expect("Glyph was just cached");
+        *last_used = self.clock;
+        glyph
+    }
+
+    // Drops glyphs oldest first until a quarter of the budget is free, so a
+    // full cache doesn't scan on every miss. `keep` is never evicted.
+    fn evict(&mut self, keep: GlyphKey) {
+        if self.bytes <= self.capacity_bytes {
+            return;
+        }
+        let target = self.capacity_bytes - self.capacity_bytes / 4;
+        let mut by_age: Vec<(u64, GlyphKey)> = self
+            .entries
+            .iter()
+            .filter(|(key, _)| **key != keep)
+            .map(|(key, (_, last_used))| (*last_used, *key))
+            .collect();
+        by_age.sort_unstable_by_key(|(last_used, _)| *last_used);
+
+        for (_, key) in by_age {
+            if self.bytes <= target {
+                break;
+            }
+            if let Some((glyph, _)) = self.entries.remove(&key) {
+                self.bytes -= glyph.bitmap.len();
+            }
+        }
+    }
+}
+
+impl Default for GlyphCache {
+    fn default() -> Self {
+        GlyphCache::new(DEFAULT_GLYPH_CACHE_BYTES)
+    }
+}
+
+#[derive(Debug, Clone, Copy)]
+pub struct TextStyle {
+    pub font: FontId,
+    pub size: f64,
+    pub align: TextAlign,
+    pub color: Color,
+}
+
+impl TextStyle {
+    pub fn new(font: FontId, size: f64, align: TextAlign) -> Self {
+        TextStyle {
+            font,
+            size,
+            align,
+            color: Color::BLACK,
+        }
+    }
+
+    pub fn with_color(mut self, color: Color) -> Self {
+        self.color = color;
+        self
+    }
+}
+
+// Blends the glyph's coverage straight into the pixmap's premultiplied RGBA
+// data, with (x, y) the pen position on the baseline
 pub fn draw_glyph(
     pixmap: &mut Pixmap,
-    cache: &mut HashMap<char, CachedGlyph>,
-    font: &Font,
-    c: char,
-    x: &mut f64,
-    y: &mut f64,
-    font_size: f64,
+    cache: &mut GlyphCache,
+    fonts: &Fonts,
+    key: GlyphKey,
+    color: Color,
+    x: f64,
+    y: f64,
 ) {
-    let cached_glyph = cache.entry(c).or_insert_with(|| {
-        let (metrics, bitmap) = font.rasterize(c, font_size as f32);
-        CachedGlyph { metrics, bitmap }
-    });
-
-    let glyph_x = *x + cached_glyph.metrics.xmin as f64;
-
-    let mut glyph_y = *y - cached_glyph.metrics.height as f64;
-
-    if cached_glyph.metrics.ymin < 0 {
-        glyph_y -= cached_glyph.metrics.ymin as f64
-    }
-
-    for row in 0..cached_glyph.metrics.height {
-        for col in 0..cached_glyph.metrics.width {
-            let index = row * cached_glyph.metrics.width + col;
-            if index < cached_glyph.bitmap.len() {
-                let alpha = cached_glyph.bitmap[index];
-                if alpha > 0 {
-                    let color = Color::from_rgba8(0, 0, 0, alpha);
-                    let mut paint = Paint::default();
-                    paint.set_color(color);
-
-                    let px = glyph_x + col as f64;
-                    let py = glyph_y + row as f64;
-
-                    if px >= 0.0
-                        && py >= 0.0
-                        && px < pixmap.width() as f64
-                        && py < pixmap.height() as f64
-                    {
-                        let rect = IntRect::from_xywh(px as i32, py as i32, 1, 1);
-                        pixmap.fill_rect(
-                            rect.unwrap().to_rect(),
-                            &paint,
-                            Transform::identity(),
-                            None,
-                        );
-                    }
-                }
+    let glyph = cache.get(fonts, key);
+    let metrics = glyph.metrics;
+    let left = (x + metrics.xmin as f64).round() as i64;
+    let top = (y - (metrics.height as i64 + metrics.ymin as i64) as f64).round() as i64;
+
+    // Clip the glyph rect against the pixmap once, rather than per pixel
+    let pixmap_width = pixmap.width() as i64;
+    let x0 = left.max(0);
+    let y0 = top.max(0);
+    let x1 = (left + metrics.width as i64).min(pixmap_width);
+    let y1 = (top + metrics.height as i64).min(pixmap.height() as i64);
+    if x0 >= x1 || y0 >= y1 {
+        return;
+    }
+
+    let source = color.premultiply().to_color_u8();
+    let source = [
+        source.red() as u32,
+        source.green() as u32,
+        source.blue() as u32,
+        source.alpha() as u32,
+    ];
+    let data = pixmap.data_mut();
+
+    for py in y0..y1 {
+        let bitmap_row = (py - top) as usize * metrics.width;
+        let pixel_row = (py * pixmap_width) as usize;
+        for px in x0..x1 {
+            let coverage = glyph.bitmap[bitmap_row + (px - left) as usize] as u32;
+            if coverage == 0 {
+                continue;
+            }
+            let pixel = &mut data[(pixel_row + px as usize) * 4..][..4];
+            let inverse_alpha = 255 - mul_div_255(source[3], coverage);
+            for (channel, source) in pixel.iter_mut().zip(source) {
+                *channel = (mul_div_255(source, coverage)
+                    + mul_div_255(*channel as u32, inverse_alpha)) as u8;
             }
         }
     }
+}
 
-    *x += cached_glyph.metrics.advance_width as f64 + 2.0; // Adjust spacing
-
-    // if *x > pixmap.width() as f64 - 40.0 {
-    //     *x = 20.0;
-    //     *y += font_size as f64 + 10.0; // Adjust line spacing
-    // }
+// a * b / 255, rounded
+fn mul_div_255(a: u32, b: u32) -> u32 {
+    let product = a * b + 128;
+    (product + (product >> 8)) >> 8
 }
 
+// Lays out and draws `text` in one go, returning the box it covers. See
+// layout::layout_text for what x, y and the options mean.
 pub fn draw_text(
     pixmap: &mut Pixmap,
-    cache: &mut HashMap<char, CachedGlyph>,
-    font: &Font,
+    cache: &mut GlyphCache,
+    fonts: &Fonts,
     text: &str,
-    start_x: f64,
-    start_y: f64,
-    font_size: f64,
-    align: TextAlign,
-) {
-    let text_width: f64 = text
-        .chars()
-        .map(|c| {
-            let cached_glyph = cache.entry(c).or_insert_with(|| {
-                let (metrics, bitmap) = font.rasterize(c, font_size as f32);
-                CachedGlyph { metrics, bitmap }
-            });
-            cached_glyph.metrics.advance_width as f64
-        })
-        .sum();
-
-    let x = match align {
-        TextAlign::Left => start_x,
-        TextAlign::Center => start_x - text_width / 2.0,
-        TextAlign::Right => start_x - text_width,
-    };
-
-    let mut cursor_x = x;
-    let mut cursor_y = start_y;
-
-    for c in text.chars() {
-        draw_glyph(
-            pixmap,
-            cache,
-            &font,
-            c,
-            &mut cursor_x,
-            &mut cursor_y,
-            font_size,
-        );
-    }
+    x: f64,
+    y: f64,
+    style: TextStyle,
+) -> TextBounds {
+    draw_text_with(
+        pixmap,
+        cache,
+        fonts,
+        text,
+        (x, y),
+        style,
+        &LayoutOptions::default(),
+    )
+}
+
+pub fn draw_text_with(
+    pixmap: &mut Pixmap,
+    cache: &mut GlyphCache,
+    fonts: &Fonts,
+    text: &str,
+    (x, y): (f64, f64),
+    style: TextStyle,
+    options: &LayoutOptions,
+) -> TextBounds {
+    let layout = layout_text(cache, fonts, text, x, y, style, options);
+    draw_layout(pixmap, cache, fonts, &layout);
+    layout.bounds
 }
 
 #[allow(dead_code)]
+#[derive(Debug, Clone, Copy, PartialEq)]
 pub enum TextAlign {
     Left,
     Center,
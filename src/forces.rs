@@ -0,0 +1,342 @@
+use crate::State;
+use crate::math::Pair;
+use crate::particles::DotId;
+use crate::sph::{self, Densities, SphParams};
+
+// Something that changes how dots move. Forces only read the simulation as
+// it was at the start of the step, so the order they run in doesn't change
+// what they see.
+pub trait Force: Send + Sync {
+    // Unique within a Forces list; used to find, toggle and save it
+    fn name(&self) -> &str;
+
+    // Adds this step's change in velocity for dot `id` to `velocity`
+    fn accelerate(&self, context: &ForceContext, id: DotId, velocity: &mut Pair);
+
+    // Whether State::force_scale multiplies this force
+    fn scaled(&self) -> bool {
+        false
+    }
+}
+
+// Read-only view of one step for the forces. The positions and velocities
+// are the ones being evaluated, which for the later passes of an integrator
+// aren't the stored ones.
+pub struct ForceContext<'a> {
+    pub state: &'a State,
+    pub positions: &'a [Pair],      // By dot index
+    pub velocities: &'a [Pair],     // By dot index
+    pub densities: &'a [Densities], // This step's, by dot index
+    pub sph: SphParams,
+}
+
+impl<'a> ForceContext<'a> {
+    pub fn new(
+        state: &'a State,
+        positions: &'a [Pair],
+        velocities: &'a [Pair],
+        densities: &'a [Densities],
+    ) -> Self {
+        ForceContext {
+            state,
+            positions,
+            velocities,
+            densities,
+            sph: SphParams::from_state(state),
+        }
+    }
+
+    // Indices of the dots that might be within `radius` of `position`, from
+    // the grid; callers still check the exact distance
+    pub fn neighbors(&self, position: Pair, radius: f32) -> impl Iterator<Item = usize> + 'a {
+        self.state.grid.query_radius(position, radius)
+    }
+}
+
+pub struct ForceSlot {
+    pub force: Box<dyn Force>,
+    pub enabled: bool,
+}
+
+// The forces update_dots applies, in order
+#[derive(Default)]
+pub struct Forces {
+    pub slots: Vec<ForceSlot>,
+}
+
+impl Forces {
+    // The behavior update_dots always had
+    pub fn builtin() -> Forces {
+        let mut forces = Forces::default();
+        forces.push(Pressure);
+        forces.push(Gravity::default());
+        forces.push(Centripetal::default());
+        forces.push(Viscosity);
+        forces.push(CenterRepulsion::default());
+        forces.push(ParticleRepulsion::default());
+        forces
+    }
+
+    pub fn push(&mut self, force: impl Force + 'static) {
+        self.insert(self.slots.len(), Box::new(force));
+    }
+
+    // Replaces any force with the same name
+    pub fn insert(&mut self, index: usize, force: Box<dyn Force>) {
+        self.remove(force.name());
+        let index = index.min(self.slots.len());
+        self.slots.insert(
+            index,
+            ForceSlot {
+                force,
+                enabled: true,
+            },
+        );
+    }
+
+    pub fn remove(&mut self, name: &str) -> Option<Box<dyn Force>> {
+        let index = self.position(name)?;
+        Some(self.slots.remove(index).force)
+    }
+
+    pub fn position(&self, name: &str) -> Option<usize> {
+        self.slots.iter().position(|slot| slot.force.name() == name)
+    }
+
+    // Returns false if there's no force called `name`
+    pub fn move_to(&mut self, name: &str, index: usize) -> bool {
+        let Some(from) = self.position(name) else {
+            return false;
+        };
+        let slot = self.slots.remove(from);
+        self.slots.insert(index.min(self.slots.len()), slot);
+        true
+    }
+
+    pub fn set_enabled(&mut self, name: &str, enabled: bool) -> bool {
+        let Some(index) = self.position(name) else {
+            return false;
+        };
+        self.slots[index].enabled = enabled;
+        true
+    }
+
+    pub fn toggle(&mut self, name: &str) -> bool {
+        let enabled = self.is_enabled(name);
+        self.set_enabled(name, !enabled)
+    }
+
+    pub fn is_enabled(&self, name: &str) -> bool {
+        self.position(name)
+            .is_some_and(|index| self.slots[index].enabled)
+    }
+
+    pub fn names(&self) -> impl Iterator<Item = &str> {
+        self.slots.iter().map(|slot| slot.force.name())
+    }
+
+    // Adds every enabled force's change for one dot to `velocity`, in order.
+    // Each run of scaled forces is added up first and scaled once, as
+    // update_dots always did, so the builtin forces round the same as before.
+    pub fn accelerate(&self, context: &ForceContext, id: DotId, velocity: &mut Pair) {
+        let force_scale = context.state.force_scale;
+        let mut scaled: Option<Pair> = None;
+        for slot in self.slots.iter().filter(|slot| slot.enabled) {
+            if slot.force.scaled() {
+                let sum = scaled.get_or_insert(Pair::new(0.0, 0.0));
+                slot.force.accelerate(context, id, sum);
+                continue;
+            }
+            if let Some(sum) = scaled.take() {
+                *velocity += sum * force_scale;
+            }
+            slot.force.accelerate(context, id, velocity);
+        }
+        if let Some(sum) = scaled {
+            *velocity += sum * force_scale;
+        }
+    }
+}
+
+// Vector from a dot to the center of its cluster
+fn to_center(context: &ForceContext, id: DotId) -> Pair {
+    let state = context.state;
+    let center = state
+        .clusters
+        .get(state.dots.cluster(id))
+        .map_or(Pair::new(0.0, 0.0), |cluster| cluster.position);
+    center - context.positions[id.index()]
+}
+
+// SPH pressure and near pressure, scaled by State::force_scale
+pub struct Pressure;
+
+impl Force for Pressure {
+    fn name(&self) -> &str {
+        "pressure"
+    }
+
+    fn accelerate(&self, context: &ForceContext, id: DotId, velocity: &mut Pair) {
+        *velocity += sph::pressure_force(
+            id.index(),
+            context.positions,
+            context.densities,
+            &context.state.grid,
+            &context.sph,
+        );
+    }
+
+    fn scaled(&self) -> bool {
+        true
+    }
+}
+
+// SPH viscosity. Already a share of the velocity difference to neighbors, so
+// not force-scaled.
+pub struct Viscosity;
+
+impl Force for Viscosity {
+    fn name(&self) -> &str {
+        "viscosity"
+    }
+
+    fn accelerate(&self, context: &ForceContext, id: DotId, velocity: &mut Pair) {
+        *velocity += sph::viscosity_force(
+            id.index(),
+            context.positions,
+            context.velocities,
+            context.densities,
+            &context.state.grid,
+            &context.sph,
+        );
+    }
+}
+
+// A constant pull, scaled by State::force_scale. Zero unless set.
+pub struct Gravity {
+    pub acceleration: Pair,
+}
+
+impl Default for Gravity {
+    fn default() -> Self {
+        Gravity {
+            acceleration: Pair::new(0.0, 0.0),
+        }
+    }
+}
+
+impl Force for Gravity {
+    fn name(&self) -> &str {
+        "gravity"
+    }
+
+    fn accelerate(&self, _context: &ForceContext, _id: DotId, velocity: &mut Pair) {
+        *velocity += self.acceleration;
+    }
+
+    fn scaled(&self) -> bool {
+        true
+    }
+}
+
+// Pulls each dot toward its cluster's center as hard as a circular orbit at
+// its current speed needs, shared out over the number of dots
+pub struct Centripetal {
+    pub strength: f32,
+}
+
+impl Default for Centripetal {
+    fn default() -> Self {
+        Centripetal { strength: 0.75 }
+    }
+}
+
+impl Force for Centripetal {
+    fn name(&self) -> &str {
+        "centripetal"
+    }
+
+    fn accelerate(&self, context: &ForceContext, id: DotId, velocity: &mut Pair) {
+        let state = context.state;
+        let to_center = to_center(context, id);
+        let distance_to_center = to_center.magnitude().abs().max(0.0001);
+        let direction_to_center = to_center / distance_to_center;
+
+        let magnitude = context.velocities[id.index()].magnitude().powi(2) / distance_to_center;
+        let force = direction_to_center * magnitude * self.strength;
+        *velocity += (45.0 / state.dots.len() as f32) * force;
+    }
+
+    fn scaled(&self) -> bool {
+        true
+    }
+}
+
+// Pushes dots out of State::center_repulsive_radius around their center so
+// they don't get stuck there. `nudge` is added as well, so a dot sitting
+// exactly on the center, where there's no direction to push in, still moves.
+pub struct CenterRepulsion {
+    pub strength: f32,
+    pub nudge: Pair,
+}
+
+impl Default for CenterRepulsion {
+    fn default() -> Self {
+        CenterRepulsion {
+            strength: 0.75,
+            nudge: Pair::new(-0.02, 0.2),
+        }
+    }
+}
+
+impl Force for CenterRepulsion {
+    fn name(&self) -> &str {
+        "center-repulsion"
+    }
+
+    fn accelerate(&self, context: &ForceContext, id: DotId, velocity: &mut Pair) {
+        let to_center = to_center(context, id);
+        let distance_to_center = to_center.magnitude().abs();
+        if distance_to_center < context.state.center_repulsive_radius {
+            let direction_to_center = to_center / distance_to_center.max(0.0001);
+            *velocity += -direction_to_center * self.strength + self.nudge;
+        }
+    }
+}
+
+// Every pair of dots closer than State::particle_repulsive_radius (plus a bit
+// for the drawn size) pushes apart, more strongly the closer they are
+pub struct ParticleRepulsion {
+    pub strength: f32,
+}
+
+impl Default for ParticleRepulsion {
+    fn default() -> Self {
+        ParticleRepulsion { strength: 0.75 }
+    }
+}
+
+impl Force for ParticleRepulsion {
+    fn name(&self) -> &str {
+        "particle-repulsion"
+    }
+
+    fn accelerate(&self, context: &ForceContext, id: DotId, velocity: &mut Pair) {
+        let state = context.state;
+        let radius = state.particle_repulsive_radius + ((12.0 * state.zoom) / 5.0);
+        let positions = context.positions;
+        let position = positions[id.index()];
+
+        for other in context.neighbors(position, radius) {
+            let other_position = positions[other];
+            if position != other_position {
+                let to_other = other_position - position;
+                let distance_to_other = to_other.magnitude().abs();
+                if distance_to_other < radius {
+                    let direction_to_other = to_other / distance_to_other.max(0.0001);
+                    *velocity += -direction_to_other * self.strength / distance_to_other;
+                }
+            }
+        }
+    }
+}
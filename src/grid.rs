@@ -0,0 +1,190 @@
+use crate::math::Pair;
+
+// Upper bound on cells per particle before the grid coarsens itself, so a few
+// runaway dots can't blow up the cell table.
+const MAX_CELLS_PER_POINT: usize = 4;
+const MIN_CELLS: usize = 1024;
+
+// Uniform grid over a set of points, rebuilt from scratch every step.
+// Points are bucketed with a counting sort so every row of cells is a
+// contiguous run of `indices`, which keeps radius queries cheap even when the
+// radius spans many cells.
+pub struct SpatialGrid {
+    cell_size: f32,
+    effective_cell_size: f32,
+    origin: Pair,
+    columns: usize,
+    rows: usize,
+    positions: Vec<Pair>,
+    cell_starts: Vec<usize>,
+    indices: Vec<usize>,
+    cursor: Vec<usize>,
+}
+
+impl SpatialGrid {
+    pub fn new(cell_size: f32) -> Self {
+        let cell_size = cell_size.max(f32::EPSILON);
+        SpatialGrid {
+            cell_size,
+            effective_cell_size: cell_size,
+            origin: Pair::new(0.0, 0.0),
+            columns: 0,
+            rows: 0,
+            positions: Vec::new(),
+            cell_starts: Vec::new(),
+            indices: Vec::new(),
+            cursor: Vec::new(),
+        }
+    }
+
+    pub fn cell_size(&self) -> f32 {
+        self.cell_size
+    }
+
+    // Takes effect on the next `rebuild`.
+    pub fn set_cell_size(&mut self, cell_size: f32) {
+        self.cell_size = cell_size.max(f32::EPSILON);
+    }
+
+    pub fn len(&self) -> usize {
+        self.positions.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.positions.is_empty()
+    }
+
+    pub fn position(&self, index: usize) -> Pair {
+        self.positions[index]
+    }
+
+    pub fn rebuild<I: IntoIterator<Item = Pair>>(&mut self, positions: I) {
+        self.positions.clear();
+        self.positions.extend(positions);
+        self.cell_starts.clear();
+        self.indices.clear();
+        self.columns = 0;
+        self.rows = 0;
+
+        let Some(first) = self.positions.first() else {
+            return;
+        };
+
+        let mut min = *first;
+        let mut max = *first;
+        for p in self
+            .positions
+            .iter()
+            .filter(|p| p.x.is_finite() && p.y.is_finite())
+        {
+            min = Pair::new(min.x.min(p.x), min.y.min(p.y));
+            max = Pair::new(max.x.max(p.x), max.y.max(p.y));
+        }
+        if !(min.x.is_finite() && min.y.is_finite() && max.x.is_finite() && max.y.is_finite()) {
+            min = Pair::new(0.0, 0.0);
+            max = Pair::new(0.0, 0.0);
+        }
+
+        let max_cells = (self.positions.len() * MAX_CELLS_PER_POINT).max(MIN_CELLS);
+        let mut cell_size = self.cell_size;
+        loop {
+            // Saturating, as points far enough apart overflow the span
+            let columns = (((max.x - min.x) / cell_size) as usize).saturating_add(1);
+            let rows = (((max.y - min.y) / cell_size) as usize).saturating_add(1);
+            if columns.saturating_mul(rows) <= max_cells {
+                self.columns = columns;
+                self.rows = rows;
+                break;
+            }
+            cell_size *= 2.0;
+        }
+        self.effective_cell_size = cell_size;
+        self.origin = min;
+
+        // Counting sort: count per cell, prefix sum, then scatter.
+        let cell_count = self.columns * self.rows;
+        self.cell_starts.resize(cell_count + 1, 0);
+        for i in 0..self.positions.len() {
+            let cell = self.cell_index(self.positions[i]);
+            self.cell_starts[cell + 1] += 1;
+        }
+        for cell in 0..cell_count {
+            self.cell_starts[cell + 1] += self.cell_starts[cell];
+        }
+
+        self.cursor.clear();
+        self.cursor
+            .extend_from_slice(&self.cell_starts[..cell_count]);
+        self.indices.resize(self.positions.len(), 0);
+        for i in 0..self.positions.len() {
+            let cell = self.cell_index(self.positions[i]);
+            self.indices[self.cursor[cell]] = i;
+            self.cursor[cell] += 1;
+        }
+    }
+
+    // Indices of every point within `radius` (inclusive) of `center`.
+    pub fn query_radius(&self, center: Pair, radius: f32) -> impl Iterator<Item = usize> + '_ {
+        let radius_sq = radius * radius;
+        let rows = self.covered_cells(center, radius);
+        rows.into_iter()
+            .flat_map(move |(y_range, x0, x1)| {
+                y_range.flat_map(move |y| {
+                    let start = self.cell_starts[y * self.columns + x0];
+                    let end = self.cell_starts[y * self.columns + x1 + 1];
+                    self.indices[start..end].iter().copied()
+                })
+            })
+            .filter(move |&i| {
+                let offset = self.positions[i] - center;
+                offset.dot(offset) <= radius_sq
+            })
+    }
+
+    // Closest point within `radius` of `center`, if any.
+    pub fn nearest(&self, center: Pair, radius: f32) -> Option<usize> {
+        self.query_radius(center, radius)
+            .map(|i| (i, self.positions[i].distance(center)))
+            .min_by(|a, b| a.1.total_cmp(&b.1))
+            .map(|(i, _)| i)
+    }
+
+    fn cell_coords(&self, p: Pair) -> (usize, usize) {
+        let x = ((p.x - self.origin.x) / self.effective_cell_size).max(0.0) as usize;
+        let y = ((p.y - self.origin.y) / self.effective_cell_size).max(0.0) as usize;
+        (x.min(self.columns - 1), y.min(self.rows - 1))
+    }
+
+    fn cell_index(&self, p: Pair) -> usize {
+        let (x, y) = self.cell_coords(p);
+        y * self.columns + x
+    }
+
+    // Rows and column span of the cells overlapping the query box, or `None`
+    // if the box misses the occupied area entirely.
+    fn covered_cells(
+        &self,
+        center: Pair,
+        radius: f32,
+    ) -> Option<(std::ops::Range<usize>, usize, usize)> {
+        if self.is_empty() || radius.is_nan() || radius < 0.0 {
+            return None;
+        }
+        let min = center - Pair::new(radius, radius);
+        let max = center + Pair::new(radius, radius);
+        let extent = Pair::new(
+            self.columns as f32 * self.effective_cell_size,
+            self.rows as f32 * self.effective_cell_size,
+        );
+        if max.x < self.origin.x
+            || max.y < self.origin.y
+            || min.x > self.origin.x + extent.x
+            || min.y > self.origin.y + extent.y
+        {
+            return None;
+        }
+        let (x0, y0) = self.cell_coords(min);
+        let (x1, y1) = self.cell_coords(max);
+        Some((y0..y1 + 1, x0, x1))
+    }
+}
@@ -0,0 +1,36 @@
+use crate::render::{draw_background, draw_dots, draw_labels};
+use crate::{State, update_dots};
+use std::path::Path;
+use tiny_skia::Pixmap;
+
+// Runs the simulation without a window. Steps are the same fixed steps the
+// windowed loop takes, just back to back instead of paced by real time.
+pub fn step(state: &mut State, steps: u64) {
+    for _ in 0..steps {
+        update_dots(state);
+        state.time_info.frame_count += 1;
+    }
+    // Draw the latest step rather than blending with the one before
+    state.time_info.interpolation = 1.0;
+}
+
+// Renders the current state into a new pixmap the size of `state.window_size`.
+pub fn render(state: &mut State) -> Option<Pixmap> {
+    let mut pixmap = Pixmap::new(state.window_size.width, state.window_size.height)?;
+    draw_background(&mut pixmap, state);
+    draw_dots(&mut pixmap, state);
+    draw_labels(&mut pixmap, state);
+    Some(pixmap)
+}
+
+pub fn render_to_png(state: &mut State, path: &Path) -> Result<(), String> {
+    let pixmap = render(state).ok_or_else(|| {
+        format!(
+            "Can't render a {}x{} image",
+            state.window_size.width, state.window_size.height
+        )
+    })?;
+    pixmap
+        .save_png(path)
+        .map_err(|err| format!("Failed to write {}: {err}", path.display()))
+}
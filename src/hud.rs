@@ -0,0 +1,175 @@
+use crate::State;
+use crate::font::{REGULAR, TextAlign, TextStyle};
+use crate::layout::{LayoutOptions, VerticalAlign, draw_layout, layout_text};
+use crate::profile::Phase;
+use tiny_skia::{Color, Paint, Pixmap, Rect, Transform};
+
+const MARGIN: f32 = 10.0; // Logical pixels from the window corner
+const PADDING: f32 = 8.0; // Logical pixels around the text
+const GRAPH_WIDTH: f32 = 240.0; // Logical pixels, one column per frame
+const GRAPH_HEIGHT: f32 = 48.0;
+const FRAME_BUDGET: f32 = 1.0 / 60.0; // Seconds; frames over it are drawn red
+
+// The in-window debug overlay
+pub struct Hud {
+    pub visible: bool,
+}
+
+impl Hud {
+    pub fn new() -> Self {
+        Hud { visible: true }
+    }
+
+    pub fn toggle(&mut self) {
+        self.visible = !self.visible;
+    }
+}
+
+impl Default for Hud {
+    fn default() -> Self {
+        Hud::new()
+    }
+}
+
+pub fn hud_text(state: &State) -> String {
+    let elapsed = state.time_info.start.elapsed().as_secs_f32();
+    let frame = state.profiler.stats(None);
+    let phase_times = |phases: &[Phase]| {
+        phases
+            .iter()
+            .map(|&phase| {
+                let average = state.profiler.stats(Some(phase)).average;
+                format!("{} {:.2}", phase.name(), average * 1000.0)
+            })
+            .collect::<Vec<_>>()
+            .join("  ")
+    };
+    format!(
+        "FPS: {:.1}   Up Time: {:.1} sec\nFrame: avg {:.2}  p95 {:.2}  p99 {:.2} ms\nSim: {} ms\nDraw: {} ms\nDots: {} ({} selected)   Steps: {}   Seed: {}\nZoom: {:.1}   Camera: {:.2}x\nTarget Density: {:.2}\nPressure Multiplier: {:.2}   Near: {:.0}\nViscosity: {:.2}   Kernel Radius: {:.1}\nSpeed Scale: {:.3}\nForce Scale: {:.3}\nIntegrator: {}   Angular Momentum Drift: {:+.3e}%\nForces: {}\nEdges: {:?}",
+        state.profiler.fps(),
+        elapsed,
+        frame.average * 1000.0,
+        frame.p95 * 1000.0,
+        frame.p99 * 1000.0,
+        phase_times(&[
+            Phase::Density,
+            Phase::Forces,
+            Phase::Integration,
+            Phase::Bounds
+        ]),
+        phase_times(&[
+            Phase::Background,
+            Phase::Dots,
+            Phase::Labels,
+            Phase::Present
+        ]),
+        state.dots.len(),
+        state.selection.len(),
+        state.time_info.step_count,
+        state.seed,
+        state.zoom,
+        state.camera.scale,
+        state.target_density,
+        state.pressure_multiplier,
+        state.near_pressure_multiplier,
+        state.viscosity,
+        state.smoothing_radius,
+        state.speed_scale,
+        state.force_scale,
+        state.integrator.name(),
+        state.momentum.relative() * 100.0,
+        force_list(state),
+        state.bounds.left,
+    )
+}
+
+// Numbered for the keys that toggle them; switched off ones in brackets
+fn force_list(state: &State) -> String {
+    state
+        .forces
+        .slots
+        .iter()
+        .enumerate()
+        .map(|(index, slot)| {
+            let name = slot.force.name();
+            if slot.enabled {
+                format!("{} {name}", index + 1)
+            } else {
+                format!("{} ({name})", index + 1)
+            }
+        })
+        .collect::<Vec<_>>()
+        .join("  ")
+}
+
+// Draws the overlay in the top-left corner on a translucent panel, with a graph
+// of recent frame times under the text
+pub fn draw_hud(pixmap: &mut Pixmap, state: &mut State) {
+    let scale = state.scale_factor;
+    let text = hud_text(state);
+    let origin = ((MARGIN + PADDING) * scale) as f64;
+    let style =
+        TextStyle::new(REGULAR, (14.0 * scale).into(), TextAlign::Left).with_color(Color::WHITE);
+    let options = LayoutOptions {
+        vertical_align: VerticalAlign::Top,
+        ..LayoutOptions::default()
+    };
+    let layout = layout_text(
+        &mut state.glyph_cache,
+        &state.fonts,
+        &text,
+        origin,
+        origin,
+        style,
+        &options,
+    );
+
+    let padding = PADDING * scale;
+    let bounds = layout.bounds;
+    let graph_x = bounds.x as f32;
+    let graph_y = (bounds.y + bounds.height) as f32 + padding;
+    let graph_width = GRAPH_WIDTH * scale;
+    let graph_height = GRAPH_HEIGHT * scale;
+    let mut paint = Paint::default();
+
+    let panel_top = bounds.y as f32 - padding;
+    if let Some(rect) = Rect::from_xywh(
+        bounds.x as f32 - padding,
+        panel_top,
+        (bounds.width as f32).max(graph_width) + 2.0 * padding,
+        graph_y + graph_height + padding - panel_top,
+    ) {
+        paint.set_color_rgba8(0, 0, 0, 160);
+        pixmap.fill_rect(rect, &paint, Transform::identity(), None);
+    }
+
+    draw_layout(pixmap, &mut state.glyph_cache, &state.fonts, &layout);
+
+    // Scaled so the budget line sits halfway up unless a frame went past that
+    let columns = graph_width.max(1.0) as usize;
+    let max_time = state.profiler.stats(None).max.max(FRAME_BUDGET * 2.0);
+    let skip = state.profiler.len().saturating_sub(columns);
+    let column_width = graph_width / columns as f32;
+    for (i, sample) in state.profiler.history().skip(skip).enumerate() {
+        let height = (sample.frame_time / max_time).min(1.0) * graph_height;
+        if sample.frame_time > FRAME_BUDGET * 1.05 {
+            paint.set_color_rgba8(230, 80, 70, 255);
+        } else {
+            paint.set_color_rgba8(120, 210, 110, 255);
+        }
+        if let Some(rect) = Rect::from_xywh(
+            graph_x + i as f32 * column_width,
+            graph_y + graph_height - height,
+            column_width,
+            height,
+        ) {
+            pixmap.fill_rect(rect, &paint, Transform::identity(), None);
+        }
+    }
+
+    let budget_y = graph_y + graph_height * (1.0 - FRAME_BUDGET / max_time);
+    if let Some(rect) = Rect::from_xywh(graph_x, budget_y, graph_width, scale.max(1.0)) {
+        paint.set_color_rgba8(255, 255, 255, 140);
+        pixmap.fill_rect(rect, &paint, Transform::identity(), None);
+    }
+}
@@ -0,0 +1,411 @@
+use crate::camera::Camera;
+use crate::math::Pair;
+use crate::{State, panel, selection, snapshot};
+use serde::{Deserialize, Serialize};
+use std::path::Path;
+use winit::event::{MouseButton, VirtualKeyCode};
+
+pub const PROFILE_CSV: &str = "gushy-profile.csv"; // Written by F4
+pub const QUICK_SAVE: &str = "gushy-quicksave.toml"; // Default for State::quick_save
+pub const THROW_WINDOW: f32 = 0.1; // Seconds of drag history a throw is averaged over
+
+// The keys the app responds to
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Key {
+    Q,
+    Up,
+    Down,
+    Left,
+    Right,
+    LBracket,
+    RBracket,
+    Home,
+    A,
+    B,
+    I,
+    P,
+    Escape,
+    Key1, // 1 to 9 toggle the force in that place in State::forces
+    Key2,
+    Key3,
+    Key4,
+    Key5,
+    Key6,
+    Key7,
+    Key8,
+    Key9,
+    F3,
+    F4,
+    F5,
+    F6,
+    F9,
+}
+
+impl Key {
+    pub fn from_keycode(keycode: VirtualKeyCode) -> Option<Key> {
+        Some(match keycode {
+            VirtualKeyCode::Q => Key::Q,
+            VirtualKeyCode::Up => Key::Up,
+            VirtualKeyCode::Down => Key::Down,
+            VirtualKeyCode::Left => Key::Left,
+            VirtualKeyCode::Right => Key::Right,
+            VirtualKeyCode::LBracket => Key::LBracket,
+            VirtualKeyCode::RBracket => Key::RBracket,
+            VirtualKeyCode::Home => Key::Home,
+            VirtualKeyCode::A => Key::A,
+            VirtualKeyCode::B => Key::B,
+            VirtualKeyCode::I => Key::I,
+            VirtualKeyCode::P => Key::P,
+            VirtualKeyCode::Escape => Key::Escape,
+            VirtualKeyCode::Key1 => Key::Key1,
+            VirtualKeyCode::Key2 => Key::Key2,
+            VirtualKeyCode::Key3 => Key::Key3,
+            VirtualKeyCode::Key4 => Key::Key4,
+            VirtualKeyCode::Key5 => Key::Key5,
+            VirtualKeyCode::Key6 => Key::Key6,
+            VirtualKeyCode::Key7 => Key::Key7,
+            VirtualKeyCode::Key8 => Key::Key8,
+            VirtualKeyCode::Key9 => Key::Key9,
+            VirtualKeyCode::F3 => Key::F3,
+            VirtualKeyCode::F4 => Key::F4,
+            VirtualKeyCode::F5 => Key::F5,
+            VirtualKeyCode::F6 => Key::F6,
+            VirtualKeyCode::F9 => Key::F9,
+            _ => return None,
+        })
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Button {
+    Left,
+    Middle,
+}
+
+impl Button {
+    pub fn from_mouse_button(button: MouseButton) -> Option<Button> {
+        match button {
+            MouseButton::Left => Some(Button::Left),
+            MouseButton::Middle => Some(Button::Middle),
+            _ => None,
+        }
+    }
+}
+
+// Everything from the window that changes the simulation, in a form that can
+// be written to a file and fed back in. Positions and sizes are physical pixels.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "kind", rename_all = "lowercase")]
+pub enum InputEvent {
+    Cursor {
+        x: f32,
+        y: f32,
+    },
+    // Shift adds to the selection instead of replacing it
+    Press {
+        button: Button,
+        #[serde(default)]
+        shift: bool,
+    },
+    Release {
+        button: Button,
+    },
+    // Ctrl resizes the dots instead of zooming
+    Wheel {
+        lines: f32,
+        ctrl: bool,
+    },
+    // Presses only
+    Key {
+        key: Key,
+    },
+    Resize {
+        width: u32,
+        height: u32,
+        scale_factor: f32,
+    },
+}
+
+// Applies one event to `state`. Returns false once the user asked to quit.
+pub fn apply(state: &mut State, event: &InputEvent) -> bool {
+    match *event {
+        InputEvent::Cursor { x, y } => move_cursor(state, Pair::new(x, y)),
+        InputEvent::Press {
+            button: Button::Left,
+            shift,
+        } => press(state, shift),
+        InputEvent::Release {
+            button: Button::Left,
+        } => release(state),
+        InputEvent::Press {
+            button: Button::Middle,
+            ..
+        } => state.mouse_info.panning = true,
+        InputEvent::Release {
+            button: Button::Middle,
+        } => state.mouse_info.panning = false,
+        InputEvent::Wheel { lines, ctrl } => wheel(state, lines, ctrl),
+        InputEvent::Key { key } => return press_key(state, key),
+        InputEvent::Resize {
+            width,
+            height,
+            scale_factor,
+        } => state.resize(width, height, scale_factor),
+    }
+    true
+}
+
+fn press_key(state: &mut State, key: Key) -> bool {
+    match key {
+        Key::Q => return false,
+        Key::Up => state.speed_scale += 0.1,
+        Key::Down => state.speed_scale = (state.speed_scale - 0.1).max(0.1),
+        Key::Left => state.force_scale = (state.force_scale - 0.1).max(0.1),
+        Key::Right => state.force_scale += 0.1,
+        Key::LBracket => state.camera.rotation -= 0.1,
+        Key::RBracket => state.camera.rotation += 0.1,
+        Key::Home => state.camera = Camera::default(),
+        Key::B => {
+            let next = state.bounds.left.next();
+            state.bounds.set_edges(next);
+        }
+        Key::A => state.selection.select_all(&state.dots),
+        Key::I => state.selection.invert(&state.dots),
+        Key::Escape => state.selection.clear(),
+        Key::Key1 => toggle_force(state, 0),
+        Key::Key2 => toggle_force(state, 1),
+        Key::Key3 => toggle_force(state, 2),
+        Key::Key4 => toggle_force(state, 3),
+        Key::Key5 => toggle_force(state, 4),
+        Key::Key6 => toggle_force(state, 5),
+        Key::Key7 => toggle_force(state, 6),
+        Key::Key8 => toggle_force(state, 7),
+        Key::Key9 => toggle_force(state, 8),
+        Key::P => state.panel.toggle(),
+        Key::F3 => state.hud.toggle(),
+        Key::F4 => {
+            let path = Path::new(PROFILE_CSV);
+            match state.profiler.write_csv(path) {
+                Ok(()) => println!(
+                    "Wrote {} frames to {}",
+                    state.profiler.len(),
+                    path.display()
+                ),
+                Err(err) => eprintln!("{err}"),
+            }
+        }
+        Key::F5 => match snapshot::save(state, &state.quick_save) {
+            Ok(()) => println!("Saved {}", state.quick_save.display()),
+            Err(err) => eprintln!("{err}"),
+        },
+        Key::F6 => {
+            state.integrator = state.integrator.next();
+            state.momentum.reset();
+        }
+        Key::F9 => {
+            let path = state.quick_save.clone();
+            match snapshot::load(state, &path) {
+                Ok(()) => println!("Loaded {}", path.display()),
+                Err(err) => eprintln!("{err}"),
+            }
+        }
+    }
+    true
+}
+
+fn toggle_force(state: &mut State, index: usize) {
+    if let Some(slot) = state.forces.slots.get_mut(index) {
+        slot.enabled = !slot.enabled;
+    }
+}
+
+fn wheel(state: &mut State, lines: f32, ctrl: bool) {
+    if ctrl {
+        let zoom_factor = 1.0 + (lines * 0.05);
+        state.zoom = (state.zoom * zoom_factor).clamp(0.1, 100.0);
+    } else {
+        let viewport = state.viewport();
+        state.camera.zoom_at(
+            state.mouse_info.mouse_position,
+            1.0 + (lines * 0.1),
+            viewport,
+            state.scale_factor,
+        );
+    }
+}
+
+// Clicking a dot selects it and starts dragging the whole selection; clicking
+// empty space starts a box select. Shift keeps what was already selected.
+fn press(state: &mut State, shift: bool) {
+    let cursor = state.mouse_info.mouse_position;
+    if panel::press(state, cursor) {
+        return; // Clicks on the panel don't reach the dots
+    }
+    if !state.mouse_info.mouse_down {
+        state.mouse_info.mouse_down = true;
+        state.mouse_info.mouse_position_last = None;
+    }
+
+    let cursor_world = state.screen_to_world(state.mouse_info.scaled_mouse_position);
+    state.mouse_info.drag_history.clear();
+    record_drag(state, cursor_world);
+    // The grid is whatever the last force pass built it from, which can be
+    // predicted positions or dots removed since, so match it to the dots now
+    state.grid.rebuild(state.dots.positions().iter().copied());
+    let hit = state
+        .grid
+        .nearest(cursor_world, state.pick_radius())
+        .and_then(|index| state.dots.id(index));
+    let selection = &mut state.selection;
+    match hit {
+        Some(id) => {
+            state.focus_color = Some(state.dots.color(id));
+            if shift {
+                selection.toggle(id);
+            } else if !selection.contains(id) {
+                selection.clear();
+                selection.insert(id);
+            }
+            if selection.contains(id) {
+                selection.grab = Some(cursor_world);
+            }
+        }
+        None => {
+            if !shift {
+                selection.clear();
+            }
+            selection.marquee = Some(state.mouse_info.mouse_position);
+        }
+    }
+}
+
+// Finishes a box select, or drops the dragged group with the cursor's recent
+// velocity
+fn release(state: &mut State) {
+    panel::release(state);
+    state.mouse_info.mouse_down = false;
+    if let Some(start) = state.selection.marquee.take() {
+        select_in_box(state, start, state.mouse_info.mouse_position);
+    }
+    let grabbed = state.selection.grab.take().is_some();
+    let throw = throw_velocity(state);
+    state.mouse_info.drag_history.clear();
+
+    // update_dots moves a dot by velocity * speed_scale each step
+    let step = state.time_info.fixed_step;
+    let thrown = if state.speed_scale > 0.0 {
+        throw * (step / state.speed_scale)
+    } else {
+        Pair::new(0.0, 0.0)
+    };
+    if grabbed {
+        for &id in &state.selection.dots {
+            if state.dots.contains(id) {
+                state.dots.set_velocity(id, thrown);
+            }
+        }
+    }
+}
+
+// Adds every dot drawn inside the box between two screen points
+fn select_in_box(state: &mut State, start: Pair, end: Pair) {
+    let (min, max) = selection::marquee_rect(start, end);
+    for id in state.dots.ids() {
+        let screen = state.world_to_screen(state.dots.position(id));
+        if screen.x >= min.x && screen.x <= max.x && screen.y >= min.y && screen.y <= max.y {
+            state.selection.insert(id);
+        }
+    }
+}
+
+// Keeps samples from the last THROW_WINDOW seconds, by when each event
+// arrived: a fast mouse can report many times within one step. Recordings
+// keep the arrival times, so replays still come out the same.
+fn record_drag(state: &mut State, cursor_world: Pair) {
+    let time = state.time_info.input_time;
+    state
+        .mouse_info
+        .drag_history
+        .push_back((time, cursor_world));
+    prune_drag_history(state);
+}
+
+fn prune_drag_history(state: &mut State) {
+    let oldest = state.time_info.input_time - THROW_WINDOW as f64;
+    let history = &mut state.mouse_info.drag_history;
+    while history.front().is_some_and(|&(time, _)| time < oldest) {
+        history.pop_front();
+    }
+}
+
+// Average cursor velocity over the drag history in world units per second,
+// capped at max_throw_speed. Zero if the cursor has been still for a while.
+pub fn throw_velocity(state: &mut State) -> Pair {
+    prune_drag_history(state);
+    let history = &state.mouse_info.drag_history;
+    let (Some(&(first_time, first)), Some(&(last_time, last))) = (history.front(), history.back())
+    else {
+        return Pair::new(0.0, 0.0);
+    };
+    if last_time <= first_time {
+        return Pair::new(0.0, 0.0);
+    }
+    let velocity = (last - first) / (last_time - first_time) as f32;
+    let speed = velocity.magnitude();
+    if speed > state.max_throw_speed {
+        velocity * (state.max_throw_speed / speed)
+    } else {
+        velocity
+    }
+}
+
+fn move_cursor(state: &mut State, position: Pair) {
+    let previous_position = state.mouse_info.mouse_position;
+    state.mouse_info.mouse_position = position;
+
+    if state.mouse_info.panning {
+        let scale_factor = state.scale_factor;
+        state.camera.pan_by(
+            state.mouse_info.mouse_position - previous_position,
+            scale_factor,
+        );
+    }
+
+    // The pixmap matches the window's physical size, so no scaling is needed
+    state.mouse_info.scaled_mouse_position = state.mouse_info.mouse_position;
+    let cursor = state.mouse_info.mouse_position;
+    if panel::drag(state, cursor) {
+        return; // Moving a slider, not the dots
+    }
+    let cursor_world = state.screen_to_world(state.mouse_info.scaled_mouse_position);
+
+    let columns = state.dots.columns_mut();
+    for (distance, position) in columns
+        .distances_to_cursor
+        .iter_mut()
+        .zip(&*columns.positions)
+    {
+        *distance = cursor_world.distance(*position);
+    }
+
+    // The group moves as one, keeping each dot's offset from the cursor
+    if let Some(grab) = state.selection.grab {
+        let offset = cursor_world - grab;
+        for &id in &state.selection.dots {
+            if state.dots.contains(id) {
+                state.dots.move_by(id, offset);
+            }
+        }
+        state.selection.grab = Some(cursor_world);
+    }
+
+    if state.mouse_info.mouse_down {
+        record_drag(state, cursor_world);
+        if let Some(last_position) = &state.mouse_info.mouse_position_last {
+            state.mouse_info.mouse_delta = cursor_world - state.screen_to_world(*last_position);
+        }
+        state.mouse_info.mouse_position_last = Some(state.mouse_info.mouse_position);
+    }
+}
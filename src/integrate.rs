@@ -0,0 +1,309 @@
+use crate::forces::ForceContext;
+use crate::math::Pair;
+use crate::profile::Phase;
+use crate::sph::{self, Densities, SphParams};
+use crate::{State, parallel};
+use serde::{Deserialize, Serialize};
+use std::time::Instant;
+
+// How update_dots turns accelerations into motion. Time is measured in steps:
+// forces give the change in velocity per step, and a dot moves
+// velocity * speed_scale per step.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum Integrator {
+    #[default]
+    SemiImplicitEuler, // One force pass; velocity first, then position with the new velocity
+    VelocityVerlet, // Two force passes; averages the acceleration at both ends of the step
+    Rk4,            // Four force passes; classic fourth-order Runge-Kutta
+}
+
+impl Integrator {
+    pub const ALL: [Integrator; 3] = [
+        Integrator::SemiImplicitEuler,
+        Integrator::VelocityVerlet,
+        Integrator::Rk4,
+    ];
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Integrator::SemiImplicitEuler => "semi-implicit-euler",
+            Integrator::VelocityVerlet => "velocity-verlet",
+            Integrator::Rk4 => "rk4",
+        }
+    }
+
+    pub fn from_name(name: &str) -> Option<Integrator> {
+        Integrator::ALL
+            .into_iter()
+            .find(|integrator| integrator.name() == name)
+    }
+
+    pub fn next(self) -> Integrator {
+        match self {
+            Integrator::SemiImplicitEuler => Integrator::VelocityVerlet,
+            Integrator::VelocityVerlet => Integrator::Rk4,
+            Integrator::Rk4 => Integrator::SemiImplicitEuler,
+        }
+    }
+}
+
+// Buffers a step fills and reads instead of allocating. Their contents mean
+// nothing between steps; they're only kept for their capacity.
+#[derive(Debug, Clone, Default)]
+pub struct Scratch {
+    pub(crate) densities: Vec<Densities>,
+    pub(crate) accelerations: Vec<Pair>, // From the latest force pass, or Euler's new velocities
+    pub(crate) first_accelerations: Vec<Pair>, // Velocity Verlet's first pass
+    pub(crate) positions: Vec<Pair>,     // A stage being evaluated
+    pub(crate) velocities: Vec<Pair>,
+    pub(crate) velocity_sum: Vec<Pair>, // RK4's weighted slopes
+    pub(crate) acceleration_sum: Vec<Pair>,
+    pub(crate) alive: Vec<bool>, // Which dots the bounds kept
+}
+
+// Moves every dot one step with state.integrator. Bounds are left to the caller.
+pub fn step(state: &mut State) {
+    // Taken out so the passes can fill it while reading the rest of the state
+    let mut scratch = std::mem::take(&mut state.scratch);
+    match state.integrator {
+        Integrator::SemiImplicitEuler => semi_implicit_euler(state, &mut scratch),
+        Integrator::VelocityVerlet => velocity_verlet(state, &mut scratch),
+        Integrator::Rk4 => rk4(state, &mut scratch),
+    }
+    state.scratch = scratch;
+}
+
+// The density and force passes into `accelerations`, for `stage` (positions
+// and velocities) or, without one, the stored columns. Only the latter writes
+// this step's densities back; either way the grid is left matching the
+// positions used. With `onto_velocities` each force is added straight onto
+// the dot's velocity, giving the new velocity rather than the acceleration;
+// that rounds the way update_dots always did.
+fn force_passes(
+    state: &mut State,
+    stage: Option<(&[Pair], &[Pair])>,
+    densities: &mut Vec<Densities>,
+    accelerations: &mut Vec<Pair>,
+    onto_velocities: bool,
+) {
+    let density_start = Instant::now();
+    let params = SphParams::from_state(state);
+    let (positions, velocities) =
+        stage.unwrap_or((state.dots.positions(), state.dots.velocities()));
+    state.grid.set_cell_size(params.kernel_radius);
+    state.grid.rebuild(positions.iter().copied());
+    sph::compute_densities(positions, &state.grid, &params, densities);
+    state
+        .profiler
+        .record(Phase::Density, density_start.elapsed());
+
+    // Every force reads the dots as they are now, so they can all be worked
+    // out before anything moves
+    let forces_start = Instant::now();
+    {
+        let context = ForceContext::new(state, positions, velocities, densities);
+        parallel::map_into(accelerations, positions.len(), |i| {
+            let id = state.dots.id(i).expect("one acceleration per dot");
+            let mut acceleration = match onto_velocities {
+                true => velocities[i],
+                false => Pair::new(0.0, 0.0),
+            };
+            state.forces.accelerate(&context, id, &mut acceleration);
+            acceleration
+        });
+    }
+    if stage.is_none() {
+        let columns = state.dots.columns_mut();
+        for (density, new_density) in columns.densities.iter_mut().zip(densities.iter()) {
+            *density = new_density.density;
+        }
+    }
+    state.profiler.record(Phase::Forces, forces_start.elapsed());
+}
+
+fn semi_implicit_euler(state: &mut State, scratch: &mut Scratch) {
+    force_passes(
+        state,
+        None,
+        &mut scratch.densities,
+        &mut scratch.accelerations,
+        true,
+    );
+    let start = Instant::now();
+    let speed_scale = state.speed_scale;
+    let columns = state.dots.columns_mut();
+    columns.velocities.copy_from_slice(&scratch.accelerations);
+    let velocities = &*columns.velocities;
+    parallel::for_each_mut(columns.positions, |i, position| {
+        *position += velocities[i] * speed_scale;
+    });
+    state.profiler.record(Phase::Integration, start.elapsed());
+}
+
+// Forces here can depend on velocity, so the second pass uses the velocity
+// predicted from the first
+fn velocity_verlet(state: &mut State, scratch: &mut Scratch) {
+    let Scratch {
+        densities,
+        accelerations: second,
+        first_accelerations: first,
+        positions,
+        velocities,
+        ..
+    } = scratch;
+    force_passes(state, None, densities, first, false);
+    let start = Instant::now();
+    let speed_scale = state.speed_scale;
+    let (current_positions, current_velocities) = (state.dots.positions(), state.dots.velocities());
+    parallel::map_into(positions, current_positions.len(), |i| {
+        current_positions[i] + (current_velocities[i] + first[i] * 0.5) * speed_scale
+    });
+    parallel::map_into(velocities, current_velocities.len(), |i| {
+        current_velocities[i] + first[i]
+    });
+    state.profiler.record(Phase::Integration, start.elapsed());
+
+    force_passes(
+        state,
+        Some((positions, velocities)),
+        densities,
+        second,
+        false,
+    );
+    let start = Instant::now();
+    let columns = state.dots.columns_mut();
+    columns.positions.copy_from_slice(positions);
+    parallel::for_each_mut(columns.velocities, |i, velocity| {
+        *velocity += (first[i] + second[i]) * 0.5;
+    });
+    state.profiler.record(Phase::Integration, start.elapsed());
+}
+
+// The stored columns stay at the start of the step until the end, so each
+// stage is worked out from them. Rather than keeping all four slopes, their
+// weighted sums are built up as the stages go.
+fn rk4(state: &mut State, scratch: &mut Scratch) {
+    let Scratch {
+        densities,
+        accelerations,
+        positions,
+        velocities,
+        velocity_sum,
+        acceleration_sum,
+        ..
+    } = scratch;
+    let speed_scale = state.speed_scale;
+
+    force_passes(state, None, densities, accelerations, false);
+    let start = Instant::now();
+    velocity_sum.clear();
+    velocity_sum.extend_from_slice(state.dots.velocities());
+    acceleration_sum.clear();
+    acceleration_sum.extend_from_slice(accelerations);
+    positions.clear();
+    positions.extend_from_slice(state.dots.positions());
+    velocities.clear();
+    velocities.extend_from_slice(state.dots.velocities());
+    state.profiler.record(Phase::Integration, start.elapsed());
+
+    for (fraction, weight) in [(0.5, 2.0), (0.5, 2.0), (1.0, 1.0)] {
+        // Move the stage `fraction` of a step along the latest slope: its
+        // velocities for positions and its accelerations for velocities
+        let start = Instant::now();
+        let (start_positions, start_velocities) = (state.dots.positions(), state.dots.velocities());
+        let stage_velocities = &*velocities;
+        parallel::for_each_mut(positions, |i, position| {
+            *position = start_positions[i] + stage_velocities[i] * (speed_scale * fraction);
+        });
+        let slope = &*accelerations;
+        parallel::for_each_mut(velocities, |i, velocity| {
+            *velocity = start_velocities[i] + slope[i] * fraction;
+        });
+        state.profiler.record(Phase::Integration, start.elapsed());
+
+        force_passes(
+            state,
+            Some((positions, velocities)),
+            densities,
+            accelerations,
+            false,
+        );
+        let start = Instant::now();
+        let (stage_velocities, slope) = (&*velocities, &*accelerations);
+        parallel::for_each_mut(velocity_sum, |i, sum| *sum += stage_velocities[i] * weight);
+        parallel::for_each_mut(acceleration_sum, |i, sum| *sum += slope[i] * weight);
+        state.profiler.record(Phase::Integration, start.elapsed());
+    }
+
+    let start = Instant::now();
+    let (velocity_sum, acceleration_sum) = (&*velocity_sum, &*acceleration_sum);
+    let columns = state.dots.columns_mut();
+    parallel::for_each_mut(columns.positions, |i, position| {
+        *position += velocity_sum[i] / 6.0 * speed_scale;
+    });
+    parallel::for_each_mut(columns.velocities, |i, velocity| {
+        *velocity += acceleration_sum[i] / 6.0;
+    });
+    state.profiler.record(Phase::Integration, start.elapsed());
+}
+
+// The dots' total angular momentum, each about the center of its own
+// cluster. A pull straight toward that center, like the centripetal force,
+// can't change it, so with only such forces on what's left over is the
+// integrator's error. Pressure, repulsion, gravity, bounds and the center
+// repulsion's nudge all change it for real.
+pub fn angular_momentum(state: &State) -> AngularMomentum {
+    let dots = &state.dots;
+    let mut momentum = AngularMomentum::default();
+    for id in dots.ids() {
+        let center = state
+            .clusters
+            .get(dots.cluster(id))
+            .map_or(Pair::new(0.0, 0.0), |cluster| cluster.position);
+        let offset = dots.position(id) - center;
+        let velocity = dots.velocity(id);
+        // In f64 so rounding doesn't hide the small changes being looked for
+        let (x, y) = (offset.x as f64, offset.y as f64);
+        let (vx, vy) = (velocity.x as f64, velocity.y as f64);
+        let term = state.particle_mass as f64 * (x * vy - y * vx);
+        momentum.total += term;
+        momentum.scale += term.abs();
+    }
+    momentum
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct AngularMomentum {
+    pub total: f64,
+    pub scale: f64, // Sum of every dot's share ignoring sign, so dots orbiting opposite ways can't cancel it out
+}
+
+// Tracks angular momentum against what it was when tracking started
+#[derive(Debug, Clone, Copy, Default)]
+pub struct AngularMomentumDrift {
+    pub baseline: Option<AngularMomentum>,
+    pub latest: f64,
+}
+
+impl AngularMomentumDrift {
+    // Starts over from the next observation, e.g. after switching integrator
+    pub fn reset(&mut self) {
+        self.baseline = None;
+    }
+
+    pub fn observe(&mut self, momentum: AngularMomentum) {
+        self.baseline.get_or_insert(momentum);
+        self.latest = momentum.total;
+    }
+
+    // Change since the baseline as a fraction of its scale; 0 without one
+    pub fn relative(&self) -> f64 {
+        match self.baseline {
+            Some(baseline) if baseline.scale > 0.0 => {
+                (self.latest - baseline.total) / baseline.scale
+            }
+            _ => 0.0,
+        }
+    }
+}
@@ -0,0 +1,316 @@
+use crate::font::{Fonts, GlyphCache, GlyphKey, TextAlign, TextStyle, draw_glyph};
+use tiny_skia::Pixmap;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum VerticalAlign {
+    Top,      // y is the top of the first line
+    Middle,   // y is the middle of the block
+    Baseline, // y is the baseline of the first line
+    Bottom,   // y is the bottom of the last line
+}
+
+#[derive(Debug, Clone, Copy)]
+pub struct LayoutOptions {
+    pub max_width: Option<f64>,
+    pub wrap: bool,               // Break lines at spaces to stay within max_width
+    pub max_lines: Option<usize>, // Lines past this are dropped
+    pub line_height: f64,         // Multiple of the font's own line spacing
+    pub vertical_align: VerticalAlign,
+    pub ellipsis: bool, // Mark cut off text with an ellipsis
+}
+
+impl Default for LayoutOptions {
+    fn default() -> Self {
+        LayoutOptions {
+            max_width: None,
+            wrap: true,
+            max_lines: None,
+            line_height: 1.0,
+            vertical_align: VerticalAlign::Baseline,
+            ellipsis: false,
+        }
+    }
+}
+
+impl LayoutOptions {
+    // A single line, shortened with an ellipsis if it's wider than max_width
+    pub fn truncate(max_width: f64) -> Self {
+        LayoutOptions {
+            max_width: Some(max_width),
+            wrap: false,
+            max_lines: Some(1),
+            ellipsis: true,
+            ..LayoutOptions::default()
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct TextBounds {
+    pub x: f64,
+    pub y: f64,
+    pub width: f64,
+    pub height: f64,
+}
+
+#[derive(Debug, Clone, Copy)]
+pub struct PositionedGlyph {
+    pub key: GlyphKey,
+    pub x: f64, // Pen position
+    pub y: f64, // Baseline
+}
+
+pub struct TextLayout {
+    pub glyphs: Vec<PositionedGlyph>,
+    pub bounds: TextBounds,
+    pub lines: usize,
+    pub truncated: bool,
+    pub style: TextStyle,
+}
+
+// Works out where every glyph of `text` goes, without drawing anything, so the
+// bounds can be used first. x is interpreted through style.align and y through
+// options.vertical_align.
+pub fn layout_text(
+    cache: &mut GlyphCache,
+    fonts: &Fonts,
+    text: &str,
+    x: f64,
+    y: f64,
+    style: TextStyle,
+    options: &LayoutOptions,
+) -> TextLayout {
+    let mut measure = Measure {
+        cache,
+        fonts,
+        style,
+    };
+    let (lines, truncated) = break_lines(&mut measure, text, options);
+
+    let px = GlyphKey::new(style.font, ' ', style.size).px();
+    let (ascent, descent, line_size) = match fonts.get(style.font).horizontal_line_metrics(px) {
+        Some(metrics) => (metrics.ascent, metrics.descent, metrics.new_line_size),
+        None => (px * 0.8, px * -0.2, px * 1.2),
+    };
+    let (ascent, descent) = (ascent as f64, descent as f64);
+    let line_advance = line_size as f64 * options.line_height;
+    let height = ascent - descent + line_advance * lines.len().saturating_sub(1) as f64;
+
+    let first_baseline = match options.vertical_align {
+        VerticalAlign::Top => y + ascent,
+        VerticalAlign::Middle => y - height / 2.0 + ascent,
+        VerticalAlign::Baseline => y,
+        VerticalAlign::Bottom => y - height + ascent,
+    };
+
+    let mut glyphs = Vec::with_capacity(text.len());
+    let mut min_x = f64::INFINITY;
+    let mut max_width: f64 = 0.0;
+
+    for (i, line) in lines.iter().enumerate() {
+        let width = measure.width(line);
+        let start_x = match style.align {
+            TextAlign::Left => x,
+            TextAlign::Center => x - width / 2.0,
+            TextAlign::Right => x - width,
+        };
+        min_x = min_x.min(start_x);
+        max_width = max_width.max(width);
+
+        let baseline = first_baseline + line_advance * i as f64;
+        let mut pen = start_x;
+        let mut previous = None;
+        for c in line.chars() {
+            if let Some(previous) = previous {
+                pen += measure.kern(previous, c);
+            }
+            let key = GlyphKey::new(style.font, c, style.size);
+            glyphs.push(PositionedGlyph {
+                key,
+                x: pen,
+                y: baseline,
+            });
+            pen += measure.advance(c);
+            previous = Some(c);
+        }
+    }
+
+    TextLayout {
+        glyphs,
+        bounds: TextBounds {
+            x: if min_x.is_finite() { min_x } else { x },
+            y: first_baseline - ascent,
+            width: max_width,
+            height,
+        },
+        lines: lines.len(),
+        truncated,
+        style,
+    }
+}
+
+pub fn draw_layout(
+    pixmap: &mut Pixmap,
+    cache: &mut GlyphCache,
+    fonts: &Fonts,
+    layout: &TextLayout,
+) {
+    for glyph in &layout.glyphs {
+        draw_glyph(
+            pixmap,
+            cache,
+            fonts,
+            glyph.key,
+            layout.style.color,
+            glyph.x,
+            glyph.y,
+        );
+    }
+}
+
+struct Measure<'a> {
+    cache: &'a mut GlyphCache,
+    fonts: &'a Fonts,
+    style: TextStyle,
+}
+
+impl Measure<'_> {
+    fn advance(&mut self, c: char) -> f64 {
+        let key = GlyphKey::new(self.style.font, c, self.style.size);
+        self.cache.get(self.fonts, key).metrics.advance_width as f64
+    }
+
+    fn kern(&self, left: char, right: char) -> f64 {
+        let px = GlyphKey::new(self.style.font, left, self.style.size).px();
+        self.fonts
+            .get(self.style.font)
+            .horizontal_kern(left, right, px)
+            .unwrap_or(0.0) as f64
+    }
+
+    // Width of one line, kerning included, exactly as layout_text places it
+    fn width(&mut self, line: &str) -> f64 {
+        self.extend(0.0, None, line).0
+    }
+
+    // Width of a line `width` wide ending in `previous` once `text` is added,
+    // and its new last char. Adds in the same order as width, so building a
+    // line up piece by piece measures exactly what measuring it whole would.
+    fn extend(
+        &mut self,
+        mut width: f64,
+        mut previous: Option<char>,
+        text: &str,
+    ) -> (f64, Option<char>) {
+        for c in text.chars() {
+            if let Some(previous) = previous {
+                width += self.kern(previous, c);
+            }
+            width += self.advance(c);
+            previous = Some(c);
+        }
+        (width, previous)
+    }
+
+    fn ellipsis(&self) -> &'static str {
+        if self.fonts.get(self.style.font).lookup_glyph_index('…') != 0 {
+            "…"
+        } else {
+            "..."
+        }
+    }
+}
+
+// Splits text into the lines to draw, returning whether anything was cut off
+fn break_lines(measure: &mut Measure, text: &str, options: &LayoutOptions) -> (Vec<String>, bool) {
+    let mut lines = Vec::new();
+
+    for paragraph in text.split('\n') {
+        let Some(max_width) = options.max_width.filter(|_| options.wrap) else {
+            lines.push(paragraph.to_string());
+            continue;
+        };
+
+        // The line so far, its width and its last char
+        let mut line = String::new();
+        let mut width = 0.0;
+        let mut last = None;
+        for word in paragraph.split(' ') {
+            let candidate = if line.is_empty() {
+                measure.extend(0.0, None, word)
+            } else {
+                let (spaced, space) = measure.extend(width, last, " ");
+                measure.extend(spaced, space, word)
+            };
+            if candidate.0 <= max_width {
+                if !line.is_empty() {
+                    line.push(' ');
+                }
+                line.push_str(word);
+                (width, last) = candidate;
+                continue;
+            }
+            if !line.is_empty() {
+                lines.push(std::mem::take(&mut line));
+                (width, last) = (0.0, None);
+            }
+            // Words wider than a whole line are broken between characters
+            let mut buffer = [0; 4];
+            for c in word.chars() {
+                let (longer, _) = measure.extend(width, last, c.encode_utf8(&mut buffer));
+                if !line.is_empty() && longer > max_width {
+                    lines.push(std::mem::take(&mut line));
+                    (width, last) = measure.extend(0.0, None, c.encode_utf8(&mut buffer));
+                } else {
+                    (width, last) = (longer, Some(c));
+                }
+                line.push(c);
+            }
+        }
+        lines.push(line);
+    }
+
+    let mut truncated = false;
+    if let Some(max_lines) = options.max_lines
+        && lines.len() > max_lines
+    {
+        lines.truncate(max_lines.max(1));
+        truncated = true;
+    }
+
+    if options.ellipsis {
+        let ellipsis = measure.ellipsis();
+        let last = lines.len() - 1;
+        for (i, line) in lines.iter_mut().enumerate() {
+            let cut_off = truncated && i == last;
+            let too_wide = options
+                .max_width
+                .is_some_and(|max_width| measure.width(line) > max_width);
+            if !(cut_off || too_wide) {
+                continue;
+            }
+            truncated = true;
+            let max_width = options.max_width.unwrap_or(f64::INFINITY);
+            line.truncate(fitting_prefix(measure, line, ellipsis, max_width));
+            line.push_str(ellipsis);
+        }
+    }
+
+    (lines, truncated)
+}
+
+// Length in bytes of the longest start of `line` that fits in `max_width` with
+// `suffix` after it, measured in one pass
+fn fitting_prefix(measure: &mut Measure, line: &str, suffix: &str, max_width: f64) -> usize {
+    let mut fitting = 0;
+    let mut width = 0.0;
+    let mut last = None;
+    let mut buffer = [0; 4];
+    for (start, c) in line.char_indices() {
+        (width, last) = measure.extend(width, last, c.encode_utf8(&mut buffer));
+        if measure.extend(width, last, suffix).0 <= max_width {
+            fitting = start + c.len_utf8();
+        }
+    }
+    fitting
+}
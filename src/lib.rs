@@ -1,30 +1,86 @@
-use crate::font::CachedGlyph;
-use fontdue::Font;
-use rand::Rng;
-use std::collections::HashMap;
+use crate::bounds::Bounds;
+use crate::camera::Camera;
+use crate::font::{Fonts, GlyphCache};
+use crate::forces::Forces;
+use crate::grid::SpatialGrid;
+use crate::hud::Hud;
+use crate::input::QUICK_SAVE;
+use crate::integrate::{AngularMomentumDrift, Integrator, Scratch};
+use crate::panel::Panel;
+use crate::particles::{DotId, Particles};
+use crate::profile::{Phase, Profiler};
+use crate::scene::SpawnGroup;
+use crate::selection::Selection;
+use crate::sph::{DEFAULT_NEAR_PRESSURE_MULTIPLIER, DEFAULT_PARTICLE_MASS, DEFAULT_VISCOSITY};
+use crate::tree::Cluster;
+use rand::{Rng, SeedableRng};
+use rand_chacha::ChaCha8Rng;
+use std::collections::VecDeque;
+use std::io;
+use std::path::{Path, PathBuf};
 use std::time::Instant;
 use tiny_skia::Color;
 
+pub mod bounds;
+pub mod camera;
+pub mod cli;
 pub mod debug;
+pub mod file;
 pub mod font;
+pub mod forces;
+pub mod grid;
+pub mod headless;
+pub mod hud;
+pub mod input;
+pub mod integrate;
+pub mod layout;
 pub mod math;
+pub mod panel;
+pub mod parallel;
+pub mod particles;
+pub mod profile;
 pub mod render;
+pub mod replay;
+pub mod scene;
+pub mod selection;
+pub mod snapshot;
+pub mod sph;
+pub mod tree;
+pub mod watch;
 use crate::math::Pair;
 
 pub const CURSOR_RADIUS: f32 = 50.0;
+pub const SMOOTHING_RADIUS: f32 = 10.0; // Default for State::smoothing_radius
+pub const PICK_RADIUS: f32 = 30.0; // Logical pixels
+pub const SPAWN_DURATION: f32 = 0.5; // Seconds for a new dot to grow in
+pub const PULSE_DURATION: f32 = 0.6; // Seconds for a modified dot to settle
+
+pub const DEFAULT_FIXED_STEP: f32 = 1.0 / 60.0;
+pub const DEFAULT_MAX_SUBSTEPS: u32 = 5;
 
 pub struct TimeInfo {
     pub last_frame_time: Instant,
     pub frame_count: u64, // Frame count for debugging
     pub start: Instant,
     pub delta_time: f32,
+    // Real seconds each update_dots call stands for. Forces and motion are per
+    // step, so this sets how many steps run per second, and so how fast the
+    // dots move, not how accurately; only animations and throws read it.
+    pub fixed_step: f32,
+    pub max_substeps: u32,  // Updates allowed per frame before dropping time
+    pub accumulator: f32,   // Real time not yet simulated
+    pub step_count: u64,    // Total update_dots calls
+    pub interpolation: f32, // How far the render sits between the last two steps, 0..1
+    pub input_time: f64,    // Real seconds since start when the input being applied arrived
 }
 
 pub struct MouseInfo {
     pub mouse_down: bool,
+    pub panning: bool, // Middle button held
     pub mouse_position: Pair,
     pub mouse_position_last: Option<Pair>,
     pub mouse_delta: Pair,
+    pub drag_history: VecDeque<(f64, Pair)>, // Input time and world position of recent drag samples
     pub scaled_mouse_position: Pair,
 }
 
@@ -39,15 +95,22 @@ impl WindowSize {
     }
 }
 
+// One dot by value, for spawning and saving. State keeps its dots in a
+// Particles store instead.
 #[derive(Clone)]
 pub struct Dot {
     pub position: Pair,
     pub velocity: Pair,
     pub density: f32,
     pub color: Color,
+    pub previous_position: Pair, // Position before the last step, for interpolation
     pub distance_to_cursor: f32,
-    pub is_selected: bool,
     pub label: String,
+    pub radius_scale: f32,     // Multiplies the drawn radius
+    pub cluster: usize,        // Index into State::clusters, the center this dot orbits
+    pub path: Option<PathBuf>, // File this dot stands for, if any
+    pub spawn_progress: f32,   // Grows from 0 to 1 after the dot appears
+    pub pulse: f32,            // Jumps to 1 when the file changes, then fades
 }
 
 impl Dot {
@@ -57,9 +120,14 @@ impl Dot {
             velocity,
             density,
             color,
-            distance_to_cursor: 0.0,
-            is_selected: false,
+            previous_position: position,
+            distance_to_cursor: f32::INFINITY, // No cursor until the first CursorMoved
             label: String::from("A File Eventually..."),
+            radius_scale: 1.0,
+            cluster: 0,
+            path: None,
+            spawn_progress: 1.0,
+            pulse: 0.0,
         }
     }
     pub fn position(&self) -> Pair {
@@ -71,33 +139,64 @@ impl Dot {
 }
 
 pub struct State {
-    pub dots: Vec<Dot>, // Points for the animation
+    pub dots: Particles, // Points for the animation
     pub zoom: f32,
-    pub window_size: WindowSize,
+    pub window_size: WindowSize, // Physical pixels, matches the pixmap
+    pub scale_factor: f32,       // Physical pixels per world unit at zoom 1
     pub time_info: TimeInfo,
     pub mouse_info: MouseInfo,
     pub target_density: f32,
     pub pressure_multiplier: f32,
     pub speed_scale: f32,
     pub force_scale: f32,
+    pub smoothing_radius: f32, // Reach of the SPH kernels
+    pub particle_mass: f32,
+    pub near_pressure_multiplier: f32, // Strength of the short-range push that stops clumping
+    pub viscosity: f32,                // 0..1, how strongly neighbors match velocities
+    pub center_repulsive_radius: f32,  // Dots closer than this to their center get pushed out
+    pub particle_repulsive_radius: f32, // Dots closer than this push each other apart
+    pub max_throw_speed: f32,          // World units per second a released dot can be thrown at
     pub focus_color: Option<Color>,
-    pub font: Font,
-    pub glyph_cache: HashMap<char, CachedGlyph>,
+    pub fonts: Fonts,
+    pub glyph_cache: GlyphCache,
+    pub grid: SpatialGrid, // Neighbor lookup, rebuilt every update
+    pub bounds: Bounds,
+    pub camera: Camera,
+    pub clusters: Vec<Cluster>,
+    pub selection: Selection,
+    pub forces: Forces, // Applied in order every update
+    pub integrator: Integrator,
+    pub momentum: AngularMomentumDrift, // Angular momentum against when it was last reset
+    pub scratch: Scratch,               // Buffers update_dots reuses between steps
+    pub hud: Hud,
+    pub panel: Panel,
+    pub profiler: Profiler,
+    pub quick_save: PathBuf, // Written by F5, read by F9
+    pub seed: u64,           // What rng started from; the same seed spawns the same dots
+    pub rng: ChaCha8Rng,     // All spawning draws from this, never thread_rng
 }
 
 impl State {
+    // A fresh random seed every time; see with_seed for repeatable runs
     pub fn new(ndots: usize, window_width: u32, window_height: u32) -> State {
-        let dots = generate_dots(ndots, window_width as f32, window_height as f32, 150.0);
-        let font_data = include_bytes!("../fonts/LTInternet-Regular.ttf") as &[u8];
-        let font = Font::from_bytes(font_data, fontdue::FontSettings::default()).unwrap();
+        State::with_seed(ndots, window_width, window_height, random_seed())
+    }
+
+    // Seeds above MAX_SEED lose their top bit, so the state can always be saved
+    pub fn with_seed(ndots: usize, window_width: u32, window_height: u32, seed: u64) -> State {
+        let seed = seed & MAX_SEED;
+        let mut rng = ChaCha8Rng::seed_from_u64(seed);
+        let dots = generate_dots(ndots, 150.0, &mut rng).into_iter().collect();
         State {
             dots,
             zoom: 40.0,
             mouse_info: MouseInfo {
                 mouse_down: false,
+                panning: false,
                 mouse_position: Pair::new(0.0, 0.0),
                 mouse_position_last: None,
                 mouse_delta: Pair::new(0.0, 0.0),
+                drag_history: VecDeque::new(),
                 scaled_mouse_position: Pair::new(0.0, 0.0),
             },
             time_info: TimeInfo {
@@ -105,196 +204,232 @@ impl State {
                 frame_count: 0,
                 start: Instant::now(),
                 delta_time: 0.0,
+                fixed_step: DEFAULT_FIXED_STEP,
+                max_substeps: DEFAULT_MAX_SUBSTEPS,
+                accumulator: 0.0,
+                step_count: 0,
+                interpolation: 0.0,
+                input_time: 0.0,
             },
             window_size: WindowSize::new(window_width, window_height),
+            scale_factor: 1.0,
             target_density: 0.05,
             pressure_multiplier: 10.0,
             speed_scale: 1.0 / ndots as f32,
             force_scale: 1.0 / ndots as f32,
+            smoothing_radius: SMOOTHING_RADIUS,
+            particle_mass: DEFAULT_PARTICLE_MASS,
+            near_pressure_multiplier: DEFAULT_NEAR_PRESSURE_MULTIPLIER,
+            viscosity: DEFAULT_VISCOSITY,
+            center_repulsive_radius: CENTER_REPULSIVE_RADIUS,
+            particle_repulsive_radius: PARTICLE_REPULSIVE_RADIUS,
+            max_throw_speed: DEFAULT_MAX_THROW_SPEED,
             focus_color: None,
-            font,
-            glyph_cache: HashMap::new(),
+            fonts: Fonts::bundled(),
+            glyph_cache: GlyphCache::default(),
+            grid: SpatialGrid::new(SMOOTHING_RADIUS),
+            bounds: Bounds::default(),
+            camera: Camera::default(),
+            clusters: vec![Cluster::new(Pair::new(0.0, 0.0), PathBuf::new(), 0)],
+            selection: Selection::new(),
+            forces: Forces::builtin(),
+            integrator: Integrator::default(),
+            momentum: AngularMomentumDrift::default(),
+            scratch: Scratch::default(),
+            hud: Hud::new(),
+            panel: Panel::new(),
+            profiler: Profiler::default(),
+            quick_save: PathBuf::from(QUICK_SAVE),
+            seed,
+            rng,
+        }
+    }
+
+    // Masked to MAX_SEED as in with_seed
+    pub fn reseed(&mut self, seed: u64) {
+        let seed = seed & MAX_SEED;
+        self.seed = seed;
+        self.rng = ChaCha8Rng::seed_from_u64(seed);
+    }
+
+    // FNV-1a over every dot's position and velocity bits. Two runs with the
+    // same seed, settings and step count give the same value.
+    pub fn fingerprint(&self) -> u64 {
+        let mut hash = 0xcbf29ce484222325u64;
+        for (position, velocity) in self.dots.positions().iter().zip(self.dots.velocities()) {
+            for value in [position.x, position.y, velocity.x, velocity.y] {
+                for byte in value.to_bits().to_le_bytes() {
+                    hash = (hash ^ byte as u64).wrapping_mul(0x100000001b3);
+                }
+            }
         }
+        hash
+    }
+
+    // One dot per file under `root`, orbiting the directory that holds it
+    pub fn from_path(root: &Path, window_width: u32, window_height: u32) -> io::Result<State> {
+        let mut state = State::new(0, window_width, window_height);
+        tree::populate(&mut state, root)?;
+        Ok(state)
+    }
+
+    pub fn resize(&mut self, width: u32, height: u32, scale_factor: f32) {
+        self.window_size = WindowSize::new(width, height);
+        self.scale_factor = scale_factor.max(0.1);
+    }
+
+    // Window size in logical pixels, i.e. world units
+    pub fn logical_size(&self) -> Pair {
+        Pair::new(
+            self.window_size.width as f32 / self.scale_factor,
+            self.window_size.height as f32 / self.scale_factor,
+        )
+    }
+
+    pub fn bounds_rect(&self) -> (Pair, Pair) {
+        self.bounds.rect(self.logical_size())
+    }
+
+    // Window size in physical pixels
+    pub fn viewport(&self) -> Pair {
+        Pair::new(
+            self.window_size.width as f32,
+            self.window_size.height as f32,
+        )
+    }
+
+    // Screen positions are physical pixels, as reported by winit
+    pub fn screen_to_world(&self, screen: Pair) -> Pair {
+        self.camera
+            .screen_to_world(screen, self.viewport(), self.scale_factor)
+    }
+
+    // Picking distance in world units, a constant size on screen
+    pub fn pick_radius(&self) -> f32 {
+        PICK_RADIUS / self.camera.scale
+    }
+
+    pub fn world_to_screen(&self, world: Pair) -> Pair {
+        self.camera
+            .world_to_screen(world, self.viewport(), self.scale_factor)
     }
+
+    // Removes dots, keeping the selection pointing at the same ones
+    pub fn retain_dots<F: FnMut(&Particles, DotId) -> bool>(&mut self, mut keep: F) {
+        let kept: Vec<bool> = self.dots.ids().map(|id| keep(&self.dots, id)).collect();
+        self.dots.retain(&kept);
+        self.selection.retain(&kept, &self.dots);
+    }
+}
+
+// TOML integers are signed, so seeds stay at or below this to fit in scene,
+// snapshot and recording files
+pub const MAX_SEED: u64 = i64::MAX as u64;
+
+pub fn random_seed() -> u64 {
+    rand::thread_rng().gen_range(0..=MAX_SEED)
 }
 
-pub fn generate_dots(ndots: usize, width: f32, height: f32, orbit_radius: f32) -> Vec<Dot> {
-    let mut rng = rand::thread_rng();
-    let center = Pair::new(0.0, 0.0);
-    let speed = (orbit_radius / (ndots as f32 * 10.0)).sqrt() * 0.1; // Adjust the speed for stable orbit
-
-    (0..ndots)
-        .map(|_| {
-            let angle = rng.gen_range(1.0..3.0 * std::f32::consts::PI);
-            let position = Pair::new(
-                center.x + orbit_radius * angle.cos(),
-                center.y + orbit_radius * angle.sin(),
-            );
-            let r: u8 = (207 + rng.gen_range(-30..30)) as u8;
-            let g: u8 = (31 + rng.gen_range(-30..30)) as u8;
-            let b: u8 = (72 + rng.gen_range(-30..30)) as u8;
-            let velocity = Pair::new(-speed / 2.0 * angle.sin(), speed / 2.5 * angle.cos());
-            Dot::new(position, velocity, 0.0, Color::from_rgba8(r, g, b, 255))
-        })
-        .collect()
+pub fn generate_dots(ndots: usize, orbit_radius: f32, rng: &mut impl Rng) -> Vec<Dot> {
+    let group = SpawnGroup {
+        count: ndots,
+        radius: orbit_radius,
+        ..SpawnGroup::default()
+    };
+    group
+        .generate(0, rng)
+        .expect("Default spawn color is valid")
 }
 
 pub fn distance(p1: Pair, p2: Pair) -> f32 {
     ((p1.x - p2.x).powi(2) + (p1.y - p2.y).powi(2)).sqrt()
 }
 
-pub fn smoothing_kernel(radius: f32, distance: f32) -> f32 {
-    let volume = std::f32::consts::PI * radius.powi(8) / 4.0;
-    let smoothing = (radius * radius - distance * distance).max(0.0);
-    smoothing * smoothing * smoothing / volume
+pub const CENTER_REPULSIVE_RADIUS: f32 = 200.0; // Default for State::center_repulsive_radius
+pub const PARTICLE_REPULSIVE_RADIUS: f32 = 150.0; // Default for State::particle_repulsive_radius
+pub const DEFAULT_MAX_THROW_SPEED: f32 = 1500.0;
+
+// Measures real time since the last call and runs however many fixed steps fit.
+pub fn advance(state: &mut State) -> u32 {
+    advance_with(state, |_| {})
 }
 
-pub fn derivative_smoothing_kernel(radius: f32, distance: f32) -> f32 {
-    let f = radius * radius - distance * distance;
-    let scale = -24.0 / (std::f32::consts::PI * radius.powi(8));
-    scale * distance * f * f
+// Like advance, calling `before_step` ahead of each fixed step, e.g. to feed
+// in replayed input at the step it was recorded
+pub fn advance_with(state: &mut State, before_step: impl FnMut(&mut State)) -> u32 {
+    let now = Instant::now();
+    let delta_time = now
+        .duration_since(state.time_info.last_frame_time)
+        .as_secs_f32();
+    state.time_info.last_frame_time = now;
+    advance_by_with(state, delta_time, before_step)
 }
 
-pub fn compute_densities(dots: &[Dot], radius: f32) -> Vec<f32> {
-    let mass = 1.0 / 2000.0;
-    dots.iter()
-        .map(|dot_i| {
-            dots.iter()
-                .filter(|dot_j| distance(dot_i.position, dot_j.position) <= radius)
-                .map(|dot_j| {
-                    mass * smoothing_kernel(radius, distance(dot_i.position, dot_j.position))
-                })
-                .sum::<f32>() // Sum all nearby mass contributions
-        })
-        .collect()
+// Runs fixed steps for `delta_time` seconds of real time, returning how many ran.
+// Time beyond `max_substeps` steps is dropped so a slow frame can't snowball.
+pub fn advance_by(state: &mut State, delta_time: f32) -> u32 {
+    advance_by_with(state, delta_time, |_| {})
 }
 
-pub fn calculate_pressure(
-    dots: &[Dot],
-    center: Pair,
-    radius: f32,
-    target_density: f32,
-    pressure_multiplier: f32,
-) -> Pair {
-    let mass = 1.0 / 2000.0;
-    let mut total_pressure_force = Pair::new(0.0, 0.0);
-
-    for particle in dots.iter() {
-        let distance = (particle.position - center).magnitude().abs();
-        if distance <= radius {
-            let direction = -(particle.position - center) / distance.max(0.0001);
-            let slope = derivative_smoothing_kernel(radius, distance.max(0.0001));
-            let density = particle.density;
-
-            if density != 0.0 {
-                let pressure_force =
-                    density_to_pressure(density, target_density, pressure_multiplier)
-                        * direction
-                        * slope
-                        * mass
-                        / density;
-
-                total_pressure_force += pressure_force;
-            }
+pub fn advance_by_with(
+    state: &mut State,
+    delta_time: f32,
+    mut before_step: impl FnMut(&mut State),
+) -> u32 {
+    let step = state.time_info.fixed_step.max(f32::EPSILON);
+    state.time_info.delta_time = delta_time;
+    state.time_info.accumulator += delta_time.max(0.0);
+
+    let mut steps = 0;
+    while state.time_info.accumulator >= step {
+        if steps == state.time_info.max_substeps {
+            state.time_info.accumulator %= step;
+            break;
         }
+        before_step(state);
+        update_dots(state);
+        state.time_info.accumulator -= step;
+        steps += 1;
     }
 
-    total_pressure_force
+    state.time_info.interpolation = (state.time_info.accumulator / step).clamp(0.0, 1.0);
+    steps
 }
 
-pub const CENTER_REPULSIVE_RADIUS: f32 = 200.0; //
-pub const PARTICLE_REPULSIVE_RADIUS: f32 = 150.0; //
-
 pub fn update_dots(state: &mut State) {
-    let gravity = Pair::new(0.0, 0.00);
-    let dots_copy = state.dots.clone(); // Avoid borrowing conflicts
-    // Compute densities first
-    let densities: Vec<f32> = compute_densities(&dots_copy, 10.0);
-
-    // Apply new densities
-    for (dot, new_density) in state.dots.iter_mut().zip(densities) {
-        dot.density = new_density;
+    state.time_info.step_count += 1;
+    let step = state.time_info.fixed_step;
+    let columns = state.dots.columns_mut();
+    columns
+        .previous_positions
+        .copy_from_slice(columns.positions);
+    for progress in columns.spawn_progress.iter_mut() {
+        *progress = (*progress + step / SPAWN_DURATION).min(1.0);
     }
-
-    // Define the center of the screen
-    let center = Pair::new(0.0, 0.0);
-    let circular_force_strength = 0.75; // Adjust the strength of the circular force
-    let repulsive_force_strength = 0.75; // Adjust the strength of the repulsive force
-
-    // Compute pressure forces and update positions
-    for dot in state.dots.iter_mut() {
-        let pressure_force = calculate_pressure(
-            &dots_copy,
-            dot.position,
-            10.0,
-            state.target_density,
-            state.pressure_multiplier,
-        );
-
-        // Calculate the direction to the center
-        let to_center = center - dot.position;
-        let distance_to_center = to_center.magnitude().abs();
-        let direction_to_center = to_center / distance_to_center.max(0.0001);
-
-        // Calculate the required centripetal force for circular motion
-        let centripetal_force_magnitude = dot.velocity.magnitude().powi(2) / distance_to_center;
-        let centripetal_force =
-            direction_to_center * centripetal_force_magnitude * circular_force_strength;
-
-        // Apply the centripetal force
-        dot.velocity +=
-            (pressure_force + gravity + ((45.0 / dots_copy.len() as f32) * centripetal_force))
-                * state.force_scale;
-
-        // Apply a repulsive force near the center to prevent dots from getting stuck
-        if distance_to_center < CENTER_REPULSIVE_RADIUS {
-            let repulsive_force = -direction_to_center * repulsive_force_strength;
-            dot.velocity += repulsive_force + Pair::new(-0.02, 0.2);
-        }
-
-        for other_dot in &dots_copy {
-            if dot.position != other_dot.position {
-                let to_other = other_dot.position - dot.position;
-                let distance_to_other = to_other.magnitude().abs();
-                if distance_to_other < PARTICLE_REPULSIVE_RADIUS + ((12.0 * state.zoom) / 5.0) {
-                    let direction_to_other = to_other / distance_to_other.max(0.0001);
-                    let repulsive_force =
-                        -direction_to_other * repulsive_force_strength / distance_to_other;
-                    dot.velocity += repulsive_force;
-                }
-            }
-        }
-
-        dot.position += dot.velocity * state.speed_scale;
-
-        // Boundary conditions
-        let dampening_factor = 0.85;
-        let boundary_x = (800.0 / 2.0) - 40.0;
-        let boundary_y = (600.0 / 2.0) - 40.0;
-
-        if dot.position.x >= boundary_x {
-            dot.position.x = boundary_x - 0.5; // Move slightly away
-            dot.velocity.x = -dot.velocity.x * dampening_factor;
-            dot.velocity.y *= dampening_factor; // Reduce y velocity to avoid getting stuck in corners
-        } else if dot.position.x <= -boundary_x {
-            dot.position.x = -boundary_x + 0.5;
-            dot.velocity.x = -dot.velocity.x * dampening_factor;
-            dot.velocity.y *= dampening_factor; // Reduce y velocity to avoid getting stuck in corners
-        }
-
-        if dot.position.y >= boundary_y {
-            dot.position.y = boundary_y - 0.5;
-            dot.velocity.y = -dot.velocity.y * dampening_factor;
-            dot.velocity.x *= dampening_factor; // Reduce x velocity to avoid getting stuck in corners
-        } else if dot.position.y <= -boundary_y {
-            dot.position.y = -boundary_y + 0.5;
-            dot.velocity.y = -dot.velocity.y * dampening_factor;
-            dot.velocity.x *= dampening_factor; // Reduce x velocity to avoid getting stuck in corners
-        }
+    for pulse in columns.pulses.iter_mut() {
+        *pulse = (*pulse - step / PULSE_DURATION).max(0.0);
     }
-}
 
-pub fn density_to_pressure(density: f32, target_density: f32, pressure_multiplier: f32) -> f32 {
-    let density_error = density - target_density;
-    density_error * pressure_multiplier
+    integrate::step(state);
+
+    let start = Instant::now();
+    let bounds = state.bounds_rect();
+    let mut alive = std::mem::take(&mut state.scratch.alive);
+    let columns = state.dots.columns_mut();
+    alive.clear();
+    alive.extend((0..columns.positions.len()).map(|i| {
+        state.bounds.apply(
+            &mut columns.positions[i],
+            &mut columns.previous_positions[i],
+            &mut columns.velocities[i],
+            bounds,
+        )
+    }));
+    if alive.contains(&false) {
+        state.retain_dots(|_, id| alive[id.index()]);
+    }
+    state.scratch.alive = alive;
+    state.profiler.record(Phase::Bounds, start.elapsed());
+    let momentum = integrate::angular_momentum(state);
+    state.momentum.observe(momentum);
 }
@@ -1,29 +1,74 @@
 use pixels::{Pixels, SurfaceTexture};
-use tiny_skia::Pixmap;
+use std::time::Instant;
+use tiny_skia::{Color, Pixmap};
 use tokio::runtime::Runtime;
 use winit::{
-    dpi::LogicalSize,
-    event::{ElementState, Event, MouseButton, MouseScrollDelta, VirtualKeyCode, WindowEvent},
-    event_loop::{ControlFlow, EventLoop},
+    dpi::{LogicalSize, PhysicalSize},
+    event::{ElementState, Event, ModifiersState, MouseScrollDelta, WindowEvent},
+    event_loop::{ControlFlow, EventLoopBuilder},
     window::WindowBuilder,
 };
 
-use gushy::{debug::print_debug, math::*, render::*, *};
+use gushy::{
+    camera::Camera,
+    cli::Args,
+    debug::print_debug,
+    hud::draw_hud,
+    input::{Button, InputEvent, Key},
+    profile::Phase,
+    render::*,
+    replay::{Recording, Replay},
+    scene::Scene,
+    watch::FsEvent,
+    *,
+};
 
 fn main() {
+    let args = match Args::parse(std::env::args().skip(1)) {
+        Ok(args) => args,
+        Err(err) => {
+            eprintln!("{err}\n\n{}", cli::USAGE);
+            std::process::exit(2);
+        }
+    };
+    if args.help {
+        println!("{}", cli::USAGE);
+        return;
+    }
+    let scene = args
+        .load_scene()
+        .unwrap_or_else(|err| exit_with_error(&err.to_string()));
+    let mut replay = args.replay.as_ref().map(|path| {
+        Replay::new(Recording::load(path).unwrap_or_else(|err| exit_with_error(&err.to_string())))
+    });
+    let (width, height) = match &replay {
+        // A recording's size is physical pixels, --size and scenes logical ones.
+        // Headless runs have a scale factor of 1, where the two are the same.
+        Some(replay) => (replay.recording.width, replay.recording.height),
+        None => args.size(scene.as_ref()),
+    };
+    if args.headless {
+        run_headless(&args, scene.as_ref(), replay, width, height);
+        return;
+    }
+    let title = scene
+        .as_ref()
+        .map_or("tiny-skia Animation", |scene| scene.window.title.as_str());
+
     // Initialize the Tokio runtime
     let runtime = Runtime::new().unwrap();
     runtime.block_on(async {
         // Create an event loop
-        let event_loop = EventLoop::new();
+        let event_loop = EventLoopBuilder::<FsEvent>::with_user_event().build();
 
         // Create a window
 
-        let window = WindowBuilder::new()
-            .with_title("tiny-skia Animation")
-            .with_inner_size(LogicalSize::new(800.0, 600.0))
-            .build(&event_loop)
-            .unwrap();
+        let builder = WindowBuilder::new().with_title(title);
+        let builder = match &replay {
+            Some(_) => builder.with_inner_size(PhysicalSize::new(width, height)),
+            None => builder.with_inner_size(LogicalSize::new(width as f64, height as f64)),
+        };
+        let window = builder.build(&event_loop).unwrap();
 
         // Get window size
         let window_size = window.inner_size();
@@ -35,11 +80,35 @@ fn main() {
             Pixels::new(window_size.width, window_size.height, surface_texture).unwrap();
 
         // Create the animation state
-        let mut state = State::new(30, window_size.width, window_size.height);
+        let mut state = args
+            .build_state(scene.as_ref(), window_size.width, window_size.height)
+            .unwrap_or_else(|err| exit_with_error(&err));
+        state.resize(
+            window_size.width,
+            window_size.height,
+            window.scale_factor() as f32,
+        );
+        if let Some(replay) = &mut replay {
+            replay.start(&mut state);
+        }
+        let mut recording = args.record.as_ref().map(|_| Recording::new(&state));
 
+        // Keep the dots in step with the directory they came from. A replay
+        // gets the changes that were recorded instead.
+        if let Some(path) = &args.path
+            && replay.is_none()
+            && let Err(err) = watch::spawn(path, event_loop.create_proxy())
+        {
+            eprintln!("Not watching {} for changes: {err}", path.display());
+        }
+
+        let mut frame_pixmap =
+            Pixmap::new(window_size.width, window_size.height).expect("Failed to create pixmap");
         let mut background_cache = Pixmap::new(window_size.width, window_size.height)
             .expect("Failed to create background cache");
-        draw_background(&mut background_cache, &state);
+        // What the cached background was drawn with; redrawn whenever this changes
+        let mut background_key: Option<(f32, Option<Color>, f32, Camera)> = None;
+        let mut modifiers = ModifiersState::empty();
         // Run the event loop
         event_loop.run(move |event, _, control_flow| {
             *control_flow = ControlFlow::Poll;
@@ -47,143 +116,270 @@ fn main() {
             match event {
                 Event::WindowEvent { event, .. } => match event {
                     WindowEvent::CloseRequested => *control_flow = ControlFlow::Exit,
-                    WindowEvent::KeyboardInput { input, .. } => {
-                        if let Some(keycode) = input.virtual_keycode {
-                            match (keycode, input.state) {
-                                (VirtualKeyCode::Q, ElementState::Pressed) => {
-                                    *control_flow = ControlFlow::Exit
-                                }
-                                (VirtualKeyCode::Up, ElementState::Pressed) => {
-                                    state.speed_scale += 0.1;
-                                }
-                                (VirtualKeyCode::Down, ElementState::Pressed) => {
-                                    state.speed_scale -= 0.1;
-                                    state.speed_scale = state.speed_scale.max(0.1);
-                                }
-                                (VirtualKeyCode::Left, ElementState::Pressed) => {
-                                    state.force_scale -= 0.1;
-                                    state.force_scale = state.force_scale.max(0.1);
-                                }
-                                (VirtualKeyCode::Right, ElementState::Pressed) => {
-                                    state.force_scale += 0.1;
-                                }
-                                _ => {}
-                            }
-                        }
+                    WindowEvent::Resized(size) => {
+                        let scale_factor = window.scale_factor() as f32;
+                        resize_surface(
+                            &mut pixels,
+                            &mut frame_pixmap,
+                            &mut background_cache,
+                            &mut state,
+                            size,
+                            scale_factor,
+                        );
+                        background_key = None;
+                        record_resize(&mut recording, &state);
                     }
-                    WindowEvent::MouseWheel {
-                        delta: MouseScrollDelta::LineDelta(_, y),
-                        ..
+                    WindowEvent::ScaleFactorChanged {
+                        scale_factor,
+                        new_inner_size,
                     } => {
-                        let zoom_factor = 1.0 + (y * 0.05);
-                        state.zoom = (state.zoom * zoom_factor).clamp(0.1, 100.0);
+                        resize_surface(
+                            &mut pixels,
+                            &mut frame_pixmap,
+                            &mut background_cache,
+                            &mut state,
+                            *new_inner_size,
+                            scale_factor as f32,
+                        );
+                        background_key = None;
+                        record_resize(&mut recording, &state);
                     }
-                    WindowEvent::MouseWheel { .. } => {} // Handle other MouseScrollDelta variants
-                    WindowEvent::MouseInput {
-                        button,
-                        state: mouse_state,
-                        ..
-                    } => {
-                        if button == MouseButton::Left {
-                            match mouse_state {
-                                ElementState::Pressed => {
-                                    if !state.mouse_info.mouse_down {
-                                        state.mouse_info.mouse_down = true;
-                                        state.mouse_info.mouse_position_last = None;
-                                    }
-
-                                    if let Some(min_dot) = state
-                                        .dots
-                                        .iter_mut()
-                                        .filter(|dot| dot.distance_to_cursor < 30.0)
-                                        .min_by(|a, b| {
-                                            a.distance_to_cursor
-                                                .partial_cmp(&b.distance_to_cursor)
-                                                .unwrap()
-                                        })
-                                    {
-                                        state.focus_color = Some(min_dot.color);
-                                        min_dot.is_selected = true;
-                                    }
-                                }
-                                ElementState::Released => {
-                                    state.mouse_info.mouse_down = false;
-                                    state
-                                        .dots
-                                        .iter_mut()
-                                        .map(|dot| {
-                                            dot.is_selected = false;
-                                            if dot.velocity().abs().magnitude() > 100.0 {
-                                                dot.velocity = Pair::new(0.0, 0.0);
-                                            }
-                                        })
-                                        .collect()
-                                }
+                    WindowEvent::ModifiersChanged(new_modifiers) => modifiers = new_modifiers,
+                    event => {
+                        let Some(input) = input_event(&event, modifiers) else {
+                            return;
+                        };
+                        if replay.is_some() {
+                            // Only quitting gets through while replaying
+                            if input == (InputEvent::Key { key: Key::Q }) {
+                                *control_flow = ControlFlow::Exit;
                             }
+                            return;
                         }
-                    }
-                    WindowEvent::CursorMoved { position, .. } => {
-                        state.mouse_info.mouse_position =
-                            Pair::new(position.x as f32, position.y as f32);
-
-                        // Calculate the scaled mouse position
-                        let adjusted_x = state.mouse_info.mouse_position.x
-                            * (800.0 / (state.window_size.width as f32));
-                        let adjusted_y = state.mouse_info.mouse_position.y
-                            * (600.0 / (state.window_size.height as f32));
-
-                        state.mouse_info.scaled_mouse_position = Pair::new(adjusted_x, adjusted_y);
-
-                        state.dots.iter_mut().for_each(|dot| {
-                            dot.distance_to_cursor = state
-                                .mouse_info
-                                .scaled_mouse_position
-                                .distance(dot.position() + Pair::new((400) as f32, (300) as f32));
-                            if dot.is_selected {
-                                dot.position = state.mouse_info.scaled_mouse_position
-                                    - Pair::new((400) as f32, (300) as f32);
-                            }
-                        });
-
-                        if state.mouse_info.mouse_down {
-                            if let Some(last_position) = &state.mouse_info.mouse_position_last {
-                                let delta = state.mouse_info.mouse_position - *last_position;
-                                let adj_x = delta.x * (800.0 / (state.window_size.width as f32));
-                                let adj_y = delta.y * (600.0 / (state.window_size.height as f32));
-
-                                state.mouse_info.mouse_delta = Pair::new(adj_x, adj_y);
-                            }
-                            state.mouse_info.mouse_position_last =
-                                Some(state.mouse_info.mouse_position);
+                        state.time_info.input_time = state.time_info.start.elapsed().as_secs_f64();
+                        if let Some(recording) = &mut recording {
+                            recording.record(&state, input);
+                        }
+                        if !input::apply(&mut state, &input) {
+                            *control_flow = ControlFlow::Exit;
                         }
                     }
-                    _ => {}
                 },
+                Event::UserEvent(fs_event) => {
+                    state.time_info.input_time = state.time_info.start.elapsed().as_secs_f64();
+                    if let Some(recording) = &mut recording {
+                        recording.record(&state, fs_event.clone());
+                    }
+                    tree::apply_fs_event(&mut state, &fs_event);
+                }
                 Event::RedrawRequested(_) => {
-                    let mut pixmap = Pixmap::new(window_size.width, window_size.height).unwrap();
+                    let key = (
+                        state.zoom,
+                        state.focus_color,
+                        state.scale_factor,
+                        state.camera,
+                    );
+                    let start = Instant::now();
+                    if background_key != Some(key) {
+                        draw_background(&mut background_cache, &state);
+                        background_key = Some(key);
+                    }
+                    frame_pixmap
+                        .data_mut()
+                        .copy_from_slice(background_cache.data());
+                    state.profiler.record(Phase::Background, start.elapsed());
 
-                    state.window_size =
-                        WindowSize::new(window.inner_size().width, window.inner_size().height);
-                    draw_background(&mut pixmap, &state);
-                    draw_dots(&mut pixmap, &mut state);
+                    let start = Instant::now();
+                    draw_dots(&mut frame_pixmap, &mut state);
+                    state.profiler.record(Phase::Dots, start.elapsed());
 
+                    let start = Instant::now();
+                    draw_labels(&mut frame_pixmap, &mut state);
+                    state.profiler.record(Phase::Labels, start.elapsed());
+                    draw_selection_box(&mut frame_pixmap, &state);
+
+                    if state.hud.visible {
+                        draw_hud(&mut frame_pixmap, &mut state);
+                    }
+                    if state.panel.visible {
+                        panel::draw_panel(&mut frame_pixmap, &mut state);
+                    }
+
+                    let start = Instant::now();
                     let frame = pixels.get_frame_mut();
 
-                    frame.copy_from_slice(pixmap.data());
+                    frame.copy_from_slice(frame_pixmap.data());
 
                     if let Err(_err) = pixels.render() {
                         *control_flow = ControlFlow::Exit;
                     }
+                    state.profiler.record(Phase::Present, start.elapsed());
                 }
                 Event::MainEventsCleared => {
                     state.time_info.frame_count += 1;
-                    update_dots(&mut state);
+                    // The previous frame ends where this one's simulation starts
+                    let frame_time = state.time_info.last_frame_time.elapsed();
+                    state.profiler.end_frame(frame_time);
+                    match &mut replay {
+                        Some(session) => {
+                            let mut running = true;
+                            advance_with(&mut state, |state| running &= session.apply_due(state));
+                            running &= session.apply_due(&mut state);
+                            if !running || session.is_finished(&state) {
+                                println!(
+                                    "Replay finished after {} steps",
+                                    state.time_info.step_count
+                                );
+                                *control_flow = ControlFlow::Exit;
+                            }
+                        }
+                        None => {
+                            advance(&mut state);
+                        }
+                    }
 
-                    print_debug(&mut state);
+                    if args.print_debug {
+                        print_debug(&mut state);
+                    }
                     window.request_redraw();
                 }
+                Event::LoopDestroyed => {
+                    if let (Some(recording), Some(path)) = (&mut recording, &args.record) {
+                        recording.finish(&state);
+                        match recording.save(path) {
+                            Ok(()) => println!(
+                                "Recorded {} events over {} steps to {}",
+                                recording.events.len(),
+                                recording.end_step - recording.start.time.step_count,
+                                path.display()
+                            ),
+                            Err(err) => eprintln!("{err}"),
+                        }
+                    }
+                }
                 _ => {}
             }
         });
     });
 }
+
+// Keeps the surface, pixel buffer, pixmaps and state in step with the window.
+// A minimized, zero-sized window leaves everything as it was.
+fn resize_surface(
+    pixels: &mut Pixels,
+    frame_pixmap: &mut Pixmap,
+    background_cache: &mut Pixmap,
+    state: &mut State,
+    size: PhysicalSize<u32>,
+    scale_factor: f32,
+) {
+    let (Some(frame), Some(background)) = (
+        Pixmap::new(size.width, size.height),
+        Pixmap::new(size.width, size.height),
+    ) else {
+        return;
+    };
+
+    if let Err(err) = pixels.resize_surface(size.width, size.height) {
+        eprintln!("Failed to resize surface: {err}");
+        return;
+    }
+    if let Err(err) = pixels.resize_buffer(size.width, size.height) {
+        eprintln!("Failed to resize pixel buffer: {err}");
+        return;
+    }
+
+    *frame_pixmap = frame;
+    *background_cache = background;
+    state.resize(size.width, size.height, scale_factor);
+}
+
+// Converts the window events that drive the simulation; the rest are None
+fn input_event(event: &WindowEvent, modifiers: ModifiersState) -> Option<InputEvent> {
+    match event {
+        WindowEvent::KeyboardInput { input, .. } if input.state == ElementState::Pressed => {
+            let key = Key::from_keycode(input.virtual_keycode?)?;
+            Some(InputEvent::Key { key })
+        }
+        WindowEvent::MouseWheel { delta, .. } => {
+            let lines = match delta {
+                MouseScrollDelta::LineDelta(_, y) => *y,
+                MouseScrollDelta::PixelDelta(position) => position.y as f32 / 20.0,
+            };
+            Some(InputEvent::Wheel {
+                lines,
+                ctrl: modifiers.ctrl(),
+            })
+        }
+        WindowEvent::MouseInput { button, state, .. } => {
+            let button = Button::from_mouse_button(*button)?;
+            Some(match state {
+                ElementState::Pressed => InputEvent::Press {
+                    button,
+                    shift: modifiers.shift(),
+                },
+                ElementState::Released => InputEvent::Release { button },
+            })
+        }
+        WindowEvent::CursorMoved { position, .. } => Some(InputEvent::Cursor {
+            x: position.x as f32,
+            y: position.y as f32,
+        }),
+        _ => None,
+    }
+}
+
+// Resizes change how the cursor maps into the world, so replays need them too
+fn record_resize(recording: &mut Option<Recording>, state: &State) {
+    if let Some(recording) = recording {
+        recording.record(
+            state,
+            InputEvent::Resize {
+                width: state.window_size.width,
+                height: state.window_size.height,
+                scale_factor: state.scale_factor,
+            },
+        );
+    }
+}
+
+fn run_headless(
+    args: &Args,
+    scene: Option<&Scene>,
+    replay: Option<Replay>,
+    width: u32,
+    height: u32,
+) {
+    let mut state = args
+        .build_state(scene, width, height)
+        .unwrap_or_else(|err| exit_with_error(&err));
+    match replay {
+        Some(mut replay) => {
+            replay.start(&mut state);
+            replay.run(&mut state);
+        }
+        None => headless::step(&mut state, args.steps),
+    }
+    if let Err(err) = headless::render_to_png(&mut state, &args.output) {
+        exit_with_error(&err);
+    }
+    if let Some(path) = &args.save_snapshot
+        && let Err(err) = snapshot::save(&state, path)
+    {
+        exit_with_error(&err.to_string());
+    }
+    println!(
+        "Wrote {} after {} steps (seed {}, fingerprint {:016x}, {}, angular momentum drift {:+.3e}%)",
+        args.output.display(),
+        state.time_info.step_count,
+        state.seed,
+        state.fingerprint(),
+        state.integrator.name(),
+        state.momentum.relative() * 100.0
+    );
+}
+
+fn exit_with_error(err: &str) -> ! {
+    eprintln!("{err}");
+    std::process::exit(1);
+}
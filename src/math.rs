@@ -1,11 +1,25 @@
+use serde::{Deserialize, Serialize};
 use std::ops::{Add, AddAssign, Div, DivAssign, Mul, MulAssign, Neg, Sub, SubAssign};
 
-#[derive(Debug, Copy, Clone, PartialEq)]
+#[derive(Debug, Copy, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(from = "[f32; 2]", into = "[f32; 2]")] // Saved as [x, y]
 pub struct Pair {
     pub x: f32,
     pub y: f32,
 }
 
+impl From<[f32; 2]> for Pair {
+    fn from([x, y]: [f32; 2]) -> Self {
+        Pair { x, y }
+    }
+}
+
+impl From<Pair> for [f32; 2] {
+    fn from(pair: Pair) -> Self {
+        [pair.x, pair.y]
+    }
+}
+
 impl Pair {
     pub fn new(x: f32, y: f32) -> Self {
         Pair { x, y }
@@ -0,0 +1,374 @@
+use crate::State;
+use crate::font::{REGULAR, TextAlign, TextStyle, draw_text};
+use crate::math::Pair;
+use tiny_skia::{Color, Paint, Pixmap, Rect, Transform};
+
+const MARGIN: f32 = 10.0; // Logical pixels from the window corner
+const PADDING: f32 = 8.0;
+const WIDTH: f32 = 260.0; // Logical pixels, padding included
+const ROW_HEIGHT: f32 = 38.0; // Label line plus track
+const BUTTON_SIZE: f32 = 16.0; // The - and + steppers
+const TRACK_HEIGHT: f32 = 4.0;
+const STEP: f32 = 0.02; // Fraction of the track one stepper click moves
+
+// One tunable, read and written through plain functions so the panel can hold
+// every slider without borrowing State
+#[derive(Clone, Copy)]
+pub struct Slider {
+    pub label: &'static str,
+    pub min: f32,
+    pub max: f32,
+    pub logarithmic: bool, // For values spanning orders of magnitude; min must be > 0
+    pub get: fn(&State) -> f32,
+    pub set: fn(&mut State, f32),
+}
+
+impl Slider {
+    // Position of `value` along the track, 0..1
+    pub fn to_fraction(&self, value: f32) -> f32 {
+        let fraction = if self.logarithmic {
+            (value.max(self.min) / self.min).ln() / (self.max / self.min).ln()
+        } else {
+            (value - self.min) / (self.max - self.min)
+        };
+        fraction.clamp(0.0, 1.0)
+    }
+
+    pub fn from_fraction(&self, fraction: f32) -> f32 {
+        let fraction = fraction.clamp(0.0, 1.0);
+        if self.logarithmic {
+            self.min * (self.max / self.min).powf(fraction)
+        } else {
+            self.min + (self.max - self.min) * fraction
+        }
+    }
+}
+
+// A column of sliders in the top-right corner of the window
+pub struct Panel {
+    pub visible: bool,
+    pub sliders: Vec<Slider>,
+    pub dragging: Option<usize>, // Slider whose handle is held
+}
+
+impl Panel {
+    pub fn new() -> Self {
+        Panel {
+            visible: false,
+            sliders: default_sliders(),
+            dragging: None,
+        }
+    }
+
+    pub fn toggle(&mut self) {
+        self.visible = !self.visible;
+        self.dragging = None;
+    }
+}
+
+impl Default for Panel {
+    fn default() -> Self {
+        Panel::new()
+    }
+}
+
+pub fn default_sliders() -> Vec<Slider> {
+    vec![
+        Slider {
+            label: "Target Density",
+            min: 0.001,
+            max: 1.0,
+            logarithmic: true,
+            get: |state| state.target_density,
+            set: |state, value| state.target_density = value,
+        },
+        Slider {
+            label: "Pressure Multiplier",
+            min: 0.0,
+            max: 100.0,
+            logarithmic: false,
+            get: |state| state.pressure_multiplier,
+            set: |state, value| state.pressure_multiplier = value,
+        },
+        Slider {
+            label: "Near Pressure Multiplier",
+            min: 1.0,
+            max: 1_000_000.0,
+            logarithmic: true,
+            get: |state| state.near_pressure_multiplier,
+            set: |state, value| state.near_pressure_multiplier = value,
+        },
+        Slider {
+            label: "Viscosity",
+            min: 0.0,
+            max: 1.0,
+            logarithmic: false,
+            get: |state| state.viscosity,
+            set: |state, value| state.viscosity = value,
+        },
+        Slider {
+            label: "Speed Scale",
+            min: 0.0001,
+            max: 5.0,
+            logarithmic: true,
+            get: |state| state.speed_scale,
+            set: |state, value| state.speed_scale = value,
+        },
+        Slider {
+            label: "Force Scale",
+            min: 0.0001,
+            max: 5.0,
+            logarithmic: true,
+            get: |state| state.force_scale,
+            set: |state, value| state.force_scale = value,
+        },
+        Slider {
+            label: "Zoom",
+            min: 0.1,
+            max: 100.0,
+            logarithmic: true,
+            get: |state| state.zoom,
+            set: |state, value| state.zoom = value,
+        },
+        Slider {
+            label: "Smoothing Radius",
+            min: 1.0,
+            max: 100.0,
+            logarithmic: false,
+            get: |state| state.smoothing_radius,
+            set: |state, value| state.smoothing_radius = value,
+        },
+        Slider {
+            label: "Particle Mass",
+            min: 0.00001,
+            max: 0.1,
+            logarithmic: true,
+            get: |state| state.particle_mass,
+            set: |state, value| state.particle_mass = value,
+        },
+        Slider {
+            label: "Center Repulsive Radius",
+            min: 0.0,
+            max: 1000.0,
+            logarithmic: false,
+            get: |state| state.center_repulsive_radius,
+            set: |state, value| state.center_repulsive_radius = value,
+        },
+        Slider {
+            label: "Particle Repulsive Radius",
+            min: 0.0,
+            max: 1000.0,
+            logarithmic: false,
+            get: |state| state.particle_repulsive_radius,
+            set: |state, value| state.particle_repulsive_radius = value,
+        },
+        Slider {
+            label: "Max Throw Speed",
+            min: 0.0,
+            max: 5000.0,
+            logarithmic: false,
+            get: |state| state.max_throw_speed,
+            set: |state, value| state.max_throw_speed = value,
+        },
+        Slider {
+            label: "Edge Damping",
+            min: 0.0,
+            max: 1.0,
+            logarithmic: false,
+            get: |state| state.bounds.damping,
+            set: |state, value| state.bounds.damping = value,
+        },
+    ]
+}
+
+// Where one slider's parts sit on screen, in physical pixels
+struct RowLayout {
+    label: Pair, // Baseline start of the label text
+    minus: Rect,
+    track: Rect,
+    plus: Rect,
+}
+
+fn panel_rect(state: &State) -> Option<Rect> {
+    let scale = state.scale_factor;
+    let width = WIDTH * scale;
+    let height = (2.0 * PADDING + ROW_HEIGHT * state.panel.sliders.len() as f32) * scale;
+    let x = state.window_size.width as f32 - (MARGIN * scale) - width;
+    Rect::from_xywh(x, MARGIN * scale, width, height)
+}
+
+fn row_layout(panel: Rect, row: usize, scale: f32) -> Option<RowLayout> {
+    let left = panel.x() + PADDING * scale;
+    let right = panel.right() - PADDING * scale;
+    let top = panel.y() + (PADDING + ROW_HEIGHT * row as f32) * scale;
+    let button = BUTTON_SIZE * scale;
+    let controls_y = top + 18.0 * scale;
+
+    Some(RowLayout {
+        label: Pair::new(left, top + 13.0 * scale),
+        minus: Rect::from_xywh(left, controls_y, button, button)?,
+        track: Rect::from_xywh(
+            left + button + 6.0 * scale,
+            controls_y + (button - TRACK_HEIGHT * scale) / 2.0,
+            right - left - 2.0 * (button + 6.0 * scale),
+            TRACK_HEIGHT * scale,
+        )?,
+        plus: Rect::from_xywh(right - button, controls_y, button, button)?,
+    })
+}
+
+fn contains(rect: &Rect, point: Pair) -> bool {
+    point.x >= rect.left()
+        && point.x < rect.right()
+        && point.y >= rect.top()
+        && point.y < rect.bottom()
+}
+
+fn center(rect: &Rect) -> Pair {
+    Pair::new(
+        rect.x() + rect.width() / 2.0,
+        rect.y() + rect.height() / 2.0,
+    )
+}
+
+// Sets the dragged slider from a cursor x position on its track
+fn set_from_cursor(state: &mut State, index: usize, track: &Rect, cursor: Pair) {
+    let slider = state.panel.sliders[index];
+    let fraction = (cursor.x - track.left()) / track.width();
+    (slider.set)(state, slider.from_fraction(fraction));
+}
+
+// Handles a left click at `cursor` (physical pixels). Returns whether the
+// panel took it, in which case it shouldn't also pick a dot.
+pub fn press(state: &mut State, cursor: Pair) -> bool {
+    if !state.panel.visible {
+        return false;
+    }
+    let Some(panel) = panel_rect(state).filter(|panel| contains(panel, cursor)) else {
+        return false;
+    };
+    let scale = state.scale_factor;
+
+    for index in 0..state.panel.sliders.len() {
+        let Some(row) = row_layout(panel, index, scale) else {
+            continue;
+        };
+        let slider = state.panel.sliders[index];
+        let fraction = slider.to_fraction((slider.get)(state));
+        if contains(&row.minus, cursor) {
+            (slider.set)(state, slider.from_fraction(fraction - STEP));
+        } else if contains(&row.plus, cursor) {
+            (slider.set)(state, slider.from_fraction(fraction + STEP));
+        } else if cursor.x >= row.track.left()
+            && cursor.x <= row.track.right()
+            && (cursor.y - center(&row.track).y).abs() <= BUTTON_SIZE * scale / 2.0
+        {
+            state.panel.dragging = Some(index);
+            set_from_cursor(state, index, &row.track, cursor);
+        }
+    }
+    true
+}
+
+// Follows the cursor while a handle is held. Returns whether the panel took the move.
+pub fn drag(state: &mut State, cursor: Pair) -> bool {
+    let Some(index) = state.panel.dragging else {
+        return false;
+    };
+    if let Some(row) =
+        panel_rect(state).and_then(|panel| row_layout(panel, index, state.scale_factor))
+    {
+        set_from_cursor(state, index, &row.track, cursor);
+    }
+    true
+}
+
+pub fn release(state: &mut State) -> bool {
+    state.panel.dragging.take().is_some()
+}
+
+pub fn draw_panel(pixmap: &mut Pixmap, state: &mut State) {
+    let Some(panel) = panel_rect(state) else {
+        return;
+    };
+    let scale = state.scale_factor;
+    let mut paint = Paint::default();
+    paint.set_color_rgba8(0, 0, 0, 160);
+    pixmap.fill_rect(panel, &paint, Transform::identity(), None);
+
+    let text_style =
+        TextStyle::new(REGULAR, (13.0 * scale).into(), TextAlign::Left).with_color(Color::WHITE);
+    let button_style =
+        TextStyle::new(REGULAR, text_style.size, TextAlign::Center).with_color(Color::WHITE);
+
+    for index in 0..state.panel.sliders.len() {
+        let Some(row) = row_layout(panel, index, scale) else {
+            continue;
+        };
+        let slider = state.panel.sliders[index];
+        let value = (slider.get)(state);
+        let fraction = slider.to_fraction(value);
+        let held = state.panel.dragging == Some(index);
+
+        draw_text(
+            pixmap,
+            &mut state.glyph_cache,
+            &state.fonts,
+            &format!("{}: {}", slider.label, format_value(value)),
+            row.label.x.into(),
+            row.label.y.into(),
+            text_style,
+        );
+
+        paint.set_color_rgba8(255, 255, 255, 60);
+        pixmap.fill_rect(row.track, &paint, Transform::identity(), None);
+        if let Some(filled) = Rect::from_xywh(
+            row.track.x(),
+            row.track.y(),
+            row.track.width() * fraction,
+            row.track.height(),
+        ) {
+            paint.set_color_rgba8(120, 210, 110, 255);
+            pixmap.fill_rect(filled, &paint, Transform::identity(), None);
+        }
+        let handle = 10.0 * scale;
+        if let Some(rect) = Rect::from_xywh(
+            row.track.x() + row.track.width() * fraction - handle / 2.0,
+            center(&row.track).y - handle / 2.0,
+            handle,
+            handle,
+        ) {
+            if held {
+                paint.set_color_rgba8(255, 255, 255, 255);
+            } else {
+                paint.set_color_rgba8(200, 200, 200, 255);
+            }
+            pixmap.fill_rect(rect, &paint, Transform::identity(), None);
+        }
+
+        for (button, symbol) in [(row.minus, "-"), (row.plus, "+")] {
+            paint.set_color_rgba8(255, 255, 255, 40);
+            pixmap.fill_rect(button, &paint, Transform::identity(), None);
+            draw_text(
+                pixmap,
+                &mut state.glyph_cache,
+                &state.fonts,
+                symbol,
+                center(&button).x.into(),
+                (button.bottom() - 4.0 * scale).into(),
+                button_style,
+            );
+        }
+    }
+}
+
+// Enough significant digits for values from 0.0001 to 1000
+fn format_value(value: f32) -> String {
+    if value.abs() >= 100.0 {
+        format!("{value:.0}")
+    } else if value.abs() >= 1.0 {
+        format!("{value:.2}")
+    } else {
+        format!("{value:.4}")
+    }
+}
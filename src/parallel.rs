@@ -0,0 +1,45 @@
+// The per-dot loops of a step, spread across threads when built with the
+// `parallel` feature. Every item only reads shared data and writes its own
+// output, in the same order either way, so both builds give identical results.
+#[cfg(feature = "parallel")]
+use rayon::prelude::*;
+
+// Refills `items` with f(0), f(1), ... f(count - 1), in order, so a buffer
+// kept between steps is reused rather than a new one allocated
+pub fn map_into<T, F>(items: &mut Vec<T>, count: usize, f: F)
+where
+    T: Send,
+    F: Fn(usize) -> T + Sync + Send,
+{
+    items.clear();
+    #[cfg(feature = "parallel")]
+    {
+        items.par_extend((0..count).into_par_iter().map(f));
+    }
+    #[cfg(not(feature = "parallel"))]
+    {
+        items.extend((0..count).map(f));
+    }
+}
+
+// Calls f with each item and its index
+pub fn for_each_mut<T, F>(items: &mut [T], f: F)
+where
+    T: Send,
+    F: Fn(usize, &mut T) + Sync + Send,
+{
+    #[cfg(feature = "parallel")]
+    {
+        items
+            .par_iter_mut()
+            .enumerate()
+            .for_each(|(index, item)| f(index, item));
+    }
+    #[cfg(not(feature = "parallel"))]
+    {
+        items
+            .iter_mut()
+            .enumerate()
+            .for_each(|(index, item)| f(index, item));
+    }
+}
@@ -0,0 +1,360 @@
+use crate::Dot;
+use crate::math::Pair;
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicU32, Ordering};
+use tiny_skia::Color;
+
+// Handed out to each store, and again whenever dots are removed, so handles
+// from before are told apart from current ones
+static NEXT_GENERATION: AtomicU32 = AtomicU32::new(0);
+
+fn next_generation() -> u32 {
+    NEXT_GENERATION.fetch_add(1, Ordering::Relaxed)
+}
+
+// Handle to one dot in a Particles store. Pushing dots leaves it valid;
+// removing any, or replacing the store, makes it stale, and a stale handle
+// is refused rather than reaching whichever dot moved into its place.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct DotId {
+    index: usize,
+    generation: u32,
+}
+
+impl DotId {
+    // Where the dot sits in the store's columns
+    pub fn index(self) -> usize {
+        self.index
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct LabelId(pub u32);
+
+// Every distinct label once; dots hold a LabelId. Private so an id always
+// names the string it was interned as; only compact renumbers them.
+#[derive(Debug, Clone, Default)]
+pub struct Labels {
+    strings: Vec<String>,
+    ids: HashMap<String, LabelId>,
+}
+
+impl Labels {
+    pub fn intern(&mut self, label: &str) -> LabelId {
+        if let Some(&id) = self.ids.get(label) {
+            return id;
+        }
+        let id = LabelId(self.strings.len() as u32);
+        self.strings.push(label.to_string());
+        self.ids.insert(label.to_string(), id);
+        id
+    }
+
+    pub fn get(&self, id: LabelId) -> &str {
+        &self.strings[id.0 as usize]
+    }
+
+    pub fn len(&self) -> usize {
+        self.strings.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.strings.is_empty()
+    }
+
+    // Drops every string none of `ids` uses, rewriting them to match, so the
+    // table doesn't keep the labels of dots that are gone
+    pub fn compact(&mut self, ids: &mut [LabelId]) {
+        let mut remap = vec![None; self.strings.len()];
+        let mut compacted = Labels::default();
+        for id in ids {
+            let old = id.0 as usize;
+            *id = *remap[old].get_or_insert_with(|| compacted.intern(&self.strings[old]));
+        }
+        *self = compacted;
+    }
+}
+
+// The dots, one array per field so each pass only touches what it reads.
+// Every column has one entry per dot, in the same order, which is why they're
+// private: dots come and go only through push, extend and retain.
+#[derive(Debug, Clone)]
+pub struct Particles {
+    positions: Vec<Pair>,
+    previous_positions: Vec<Pair>, // Before the last step, for interpolation
+    velocities: Vec<Pair>,
+    densities: Vec<f32>,
+    colors: Vec<Color>,
+    distances_to_cursor: Vec<f32>,
+    labels: Vec<LabelId>,        // Into label_table, which holds no others
+    radius_scales: Vec<f32>,     // Multiplies the drawn radius
+    clusters: Vec<usize>,        // Index into State::clusters, the center each dot orbits
+    paths: Vec<Option<PathBuf>>, // The file each dot stands for, if any
+    spawn_progress: Vec<f32>,    // 0 when spawned, 1 once fully grown
+    pulses: Vec<f32>,            // Jump to 1 when the file changes, then fade
+    label_table: Labels,
+    generation: u32,
+}
+
+// The columns a step writes, borrowed together. Slices, so they can be
+// changed freely but not lengthened or shortened.
+pub struct ColumnsMut<'a> {
+    pub positions: &'a mut [Pair],
+    pub previous_positions: &'a mut [Pair],
+    pub velocities: &'a mut [Pair],
+    pub densities: &'a mut [f32],
+    pub distances_to_cursor: &'a mut [f32],
+    pub spawn_progress: &'a mut [f32],
+    pub pulses: &'a mut [f32],
+}
+
+impl Default for Particles {
+    fn default() -> Self {
+        Particles {
+            positions: Vec::new(),
+            previous_positions: Vec::new(),
+            velocities: Vec::new(),
+            densities: Vec::new(),
+            colors: Vec::new(),
+            distances_to_cursor: Vec::new(),
+            labels: Vec::new(),
+            radius_scales: Vec::new(),
+            clusters: Vec::new(),
+            paths: Vec::new(),
+            spawn_progress: Vec::new(),
+            pulses: Vec::new(),
+            label_table: Labels::default(),
+            generation: next_generation(),
+        }
+    }
+}
+
+impl Particles {
+    pub fn new() -> Self {
+        Particles::default()
+    }
+
+    pub fn len(&self) -> usize {
+        self.positions.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.positions.is_empty()
+    }
+
+    // The handle for the dot at `index`, if there is one
+    pub fn id(&self, index: usize) -> Option<DotId> {
+        (index < self.len()).then_some(DotId {
+            index,
+            generation: self.generation,
+        })
+    }
+
+    pub fn ids(&self) -> impl Iterator<Item = DotId> + use<> {
+        let generation = self.generation;
+        (0..self.len()).map(move |index| DotId { index, generation })
+    }
+
+    // Whether `id` is a current handle into this store
+    pub fn contains(&self, id: DotId) -> bool {
+        id.generation == self.generation && id.index < self.len()
+    }
+
+    // Index of a dot's entries, refusing stale handles
+    fn slot(&self, id: DotId) -> usize {
+        assert!(self.contains(id), "stale or foreign {id:?}");
+        id.index
+    }
+
+    pub fn push(&mut self, dot: Dot) -> DotId {
+        let index = self.len();
+        let label = self.label_table.intern(&dot.label);
+        self.positions.push(dot.position);
+        self.previous_positions.push(dot.previous_position);
+        self.velocities.push(dot.velocity);
+        self.densities.push(dot.density);
+        self.colors.push(dot.color);
+        self.distances_to_cursor.push(dot.distance_to_cursor);
+        self.labels.push(label);
+        self.radius_scales.push(dot.radius_scale);
+        self.clusters.push(dot.cluster);
+        self.paths.push(dot.path);
+        self.spawn_progress.push(dot.spawn_progress);
+        self.pulses.push(dot.pulse);
+        DotId {
+            index,
+            generation: self.generation,
+        }
+    }
+
+    // A copy of one dot, label included. For saving and the like, not per-step
+    // work.
+    pub fn get(&self, id: DotId) -> Dot {
+        let i = self.slot(id);
+        Dot {
+            position: self.positions[i],
+            velocity: self.velocities[i],
+            density: self.densities[i],
+            color: self.colors[i],
+            previous_position: self.previous_positions[i],
+            distance_to_cursor: self.distances_to_cursor[i],
+            label: self.label(id).to_string(),
+            radius_scale: self.radius_scales[i],
+            cluster: self.clusters[i],
+            path: self.paths[i].clone(),
+            spawn_progress: self.spawn_progress[i],
+            pulse: self.pulses[i],
+        }
+    }
+
+    pub fn positions(&self) -> &[Pair] {
+        &self.positions
+    }
+
+    pub fn previous_positions(&self) -> &[Pair] {
+        &self.previous_positions
+    }
+
+    pub fn velocities(&self) -> &[Pair] {
+        &self.velocities
+    }
+
+    pub fn densities(&self) -> &[f32] {
+        &self.densities
+    }
+
+    pub fn distances_to_cursor(&self) -> &[f32] {
+        &self.distances_to_cursor
+    }
+
+    pub fn columns_mut(&mut self) -> ColumnsMut<'_> {
+        ColumnsMut {
+            positions: &mut self.positions,
+            previous_positions: &mut self.previous_positions,
+            velocities: &mut self.velocities,
+            densities: &mut self.densities,
+            distances_to_cursor: &mut self.distances_to_cursor,
+            spawn_progress: &mut self.spawn_progress,
+            pulses: &mut self.pulses,
+        }
+    }
+
+    pub fn position(&self, id: DotId) -> Pair {
+        self.positions[self.slot(id)]
+    }
+
+    pub fn velocity(&self, id: DotId) -> Pair {
+        self.velocities[self.slot(id)]
+    }
+
+    pub fn set_velocity(&mut self, id: DotId, velocity: Pair) {
+        let i = self.slot(id);
+        self.velocities[i] = velocity;
+    }
+
+    pub fn color(&self, id: DotId) -> Color {
+        self.colors[self.slot(id)]
+    }
+
+    pub fn distance_to_cursor(&self, id: DotId) -> f32 {
+        self.distances_to_cursor[self.slot(id)]
+    }
+
+    pub fn cluster(&self, id: DotId) -> usize {
+        self.clusters[self.slot(id)]
+    }
+
+    pub fn path(&self, id: DotId) -> Option<&Path> {
+        self.paths[self.slot(id)].as_deref()
+    }
+
+    pub fn label(&self, id: DotId) -> &str {
+        self.label_table.get(self.labels[self.slot(id)])
+    }
+
+    pub fn set_label(&mut self, id: DotId, label: &str) {
+        let i = self.slot(id);
+        self.labels[i] = self.label_table.intern(label);
+    }
+
+    pub fn set_radius_scale(&mut self, id: DotId, radius_scale: f32) {
+        let i = self.slot(id);
+        self.radius_scales[i] = radius_scale;
+    }
+
+    // Starts the pulse animation, as when the dot's file changes
+    pub fn pulse(&mut self, id: DotId) {
+        let i = self.slot(id);
+        self.pulses[i] = 1.0;
+    }
+
+    // Moves a dot without that counting as motion, so it isn't drawn sliding
+    // there from where it was
+    pub fn move_by(&mut self, id: DotId, offset: Pair) {
+        let i = self.slot(id);
+        self.positions[i] += offset;
+        self.previous_positions[i] = self.positions[i];
+    }
+
+    // The first dot standing for `path`
+    pub fn find_path(&self, path: &Path) -> Option<DotId> {
+        let index = self.paths.iter().position(|p| p.as_deref() == Some(path))?;
+        self.id(index)
+    }
+
+    pub fn interpolated_position(&self, id: DotId, alpha: f32) -> Pair {
+        let i = self.slot(id);
+        let previous = self.previous_positions[i];
+        previous + (self.positions[i] - previous) * alpha
+    }
+
+    // Radius multiplier including the spawn and pulse animations
+    pub fn animated_radius_scale(&self, id: DotId) -> f32 {
+        let i = self.slot(id);
+        let grow = 1.0 - (1.0 - self.spawn_progress[i]).powi(3); // Ease out
+        self.radius_scales[i] * grow * (1.0 + 0.5 * self.pulses[i])
+    }
+
+    // Keeps the dots where `kept[i]` is true, in order. Missing flags keep.
+    // If any go, every handle from before is stale.
+    pub fn retain(&mut self, kept: &[bool]) {
+        fn column<T>(values: &mut Vec<T>, kept: &[bool]) {
+            let mut flags = kept.iter();
+            values.retain(|_| flags.next().copied().unwrap_or(true));
+        }
+        if kept.iter().take(self.len()).all(|&keep| keep) {
+            return;
+        }
+        column(&mut self.positions, kept);
+        column(&mut self.previous_positions, kept);
+        column(&mut self.velocities, kept);
+        column(&mut self.densities, kept);
+        column(&mut self.colors, kept);
+        column(&mut self.distances_to_cursor, kept);
+        column(&mut self.labels, kept);
+        column(&mut self.radius_scales, kept);
+        column(&mut self.clusters, kept);
+        column(&mut self.paths, kept);
+        column(&mut self.spawn_progress, kept);
+        column(&mut self.pulses, kept);
+        self.label_table.compact(&mut self.labels);
+        self.generation = next_generation();
+    }
+}
+
+impl Extend<Dot> for Particles {
+    fn extend<I: IntoIterator<Item = Dot>>(&mut self, dots: I) {
+        for dot in dots {
+            self.push(dot);
+        }
+    }
+}
+
+impl FromIterator<Dot> for Particles {
+    fn from_iter<I: IntoIterator<Item = Dot>>(dots: I) -> Self {
+        let mut particles = Particles::new();
+        particles.extend(dots);
+        particles
+    }
+}
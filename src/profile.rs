@@ -0,0 +1,209 @@
+use std::fmt::Write as _;
+use std::fs;
+use std::path::Path;
+use std::time::Duration;
+
+pub const PROFILE_FRAMES: usize = 600; // About ten seconds at 60 FPS
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Phase {
+    Density,
+    Forces,
+    Integration,
+    Bounds,
+    Background,
+    Dots,
+    Labels,
+    Present,
+}
+
+impl Phase {
+    pub const ALL: [Phase; 8] = [
+        Phase::Density,
+        Phase::Forces,
+        Phase::Integration,
+        Phase::Bounds,
+        Phase::Background,
+        Phase::Dots,
+        Phase::Labels,
+        Phase::Present,
+    ];
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Phase::Density => "density",
+            Phase::Forces => "forces",
+            Phase::Integration => "integration",
+            Phase::Bounds => "bounds",
+            Phase::Background => "background",
+            Phase::Dots => "dots",
+            Phase::Labels => "labels",
+            Phase::Present => "present",
+        }
+    }
+}
+
+// Seconds spent on one frame, in total and per phase. Simulation phases add up
+// over every step the frame ran.
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct FrameSample {
+    pub frame_time: f32,
+    pub phases: [f32; Phase::ALL.len()],
+}
+
+impl FrameSample {
+    pub fn phase(&self, phase: Phase) -> f32 {
+        self.phases[phase as usize]
+    }
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct Stats {
+    pub average: f32,
+    pub p95: f32,
+    pub p99: f32,
+    pub max: f32,
+}
+
+// The last `capacity` frames' timings in a ring buffer
+pub struct Profiler {
+    samples: Vec<FrameSample>,
+    next: usize, // Where the next sample goes once the buffer is full
+    capacity: usize,
+    current: FrameSample,
+    frame_stats: Stats, // Worked out once per frame, in end_frame
+    phase_stats: [Stats; Phase::ALL.len()], // Likewise
+    sorted: Vec<f32>,   // Reused for sorting
+}
+
+impl Profiler {
+    pub fn new(capacity: usize) -> Self {
+        Profiler {
+            samples: Vec::with_capacity(capacity),
+            next: 0,
+            capacity: capacity.max(1),
+            current: FrameSample::default(),
+            frame_stats: Stats::default(),
+            phase_stats: [Stats::default(); Phase::ALL.len()],
+            sorted: Vec::with_capacity(capacity),
+        }
+    }
+
+    pub fn record(&mut self, phase: Phase, duration: Duration) {
+        self.current.phases[phase as usize] += duration.as_secs_f32();
+    }
+
+    // Closes the frame being recorded and starts the next one
+    pub fn end_frame(&mut self, frame_time: Duration) {
+        let mut sample = std::mem::take(&mut self.current);
+        sample.frame_time = frame_time.as_secs_f32();
+        if self.samples.len() < self.capacity {
+            self.samples.push(sample);
+        } else {
+            self.samples[self.next] = sample;
+        }
+        self.next = (self.next + 1) % self.capacity;
+        self.update_stats();
+    }
+
+    pub fn len(&self) -> usize {
+        self.samples.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.samples.is_empty()
+    }
+
+    pub fn clear(&mut self) {
+        self.samples.clear();
+        self.next = 0;
+        self.current = FrameSample::default();
+        self.update_stats();
+    }
+
+    // Oldest first
+    pub fn history(&self) -> impl Iterator<Item = &FrameSample> {
+        let (newer, older) = self.samples.split_at(self.next.min(self.samples.len()));
+        older.iter().chain(newer)
+    }
+
+    pub fn latest(&self) -> Option<&FrameSample> {
+        self.history().last()
+    }
+
+    // Over whole frames, or one phase of them, as of the last end_frame
+    pub fn stats(&self, phase: Option<Phase>) -> Stats {
+        match phase {
+            Some(phase) => self.phase_stats[phase as usize],
+            None => self.frame_stats,
+        }
+    }
+
+    fn update_stats(&mut self) {
+        self.frame_stats = self.compute_stats(None);
+        for phase in Phase::ALL {
+            self.phase_stats[phase as usize] = self.compute_stats(Some(phase));
+        }
+    }
+
+    fn compute_stats(&mut self, phase: Option<Phase>) -> Stats {
+        let values = &mut self.sorted;
+        values.clear();
+        values.extend(
+            self.samples
+                .iter()
+                .map(|sample| phase.map_or(sample.frame_time, |phase| sample.phase(phase))),
+        );
+        if values.is_empty() {
+            return Stats::default();
+        }
+        values.sort_unstable_by(f32::total_cmp);
+
+        Stats {
+            average: values.iter().sum::<f32>() / values.len() as f32,
+            p95: percentile(values, 0.95),
+            p99: percentile(values, 0.99),
+            max: values[values.len() - 1],
+        }
+    }
+
+    // Rolling frames per second, from the average frame time
+    pub fn fps(&self) -> f32 {
+        let average = self.stats(None).average;
+        if average > 0.0 { 1.0 / average } else { 0.0 }
+    }
+
+    // One row per frame, oldest first, times in milliseconds
+    pub fn to_csv(&self) -> String {
+        let mut csv = String::from("frame_ms");
+        for phase in Phase::ALL {
+            let _ = write!(csv, ",{}_ms", phase.name());
+        }
+        csv.push('\n');
+        for sample in self.history() {
+            let _ = write!(csv, "{:.4}", sample.frame_time * 1000.0);
+            for time in sample.phases {
+                let _ = write!(csv, ",{:.4}", time * 1000.0);
+            }
+            csv.push('\n');
+        }
+        csv
+    }
+
+    pub fn write_csv(&self, path: &Path) -> Result<(), String> {
+        fs::write(path, self.to_csv())
+            .map_err(|err| format!("Failed to write {}: {err}", path.display()))
+    }
+}
+
+impl Default for Profiler {
+    fn default() -> Self {
+        Profiler::new(PROFILE_FRAMES)
+    }
+}
+
+// Nearest-rank percentile of sorted values
+fn percentile(sorted: &[f32], p: f32) -> f32 {
+    let rank = (p * sorted.len() as f32).ceil() as usize;
+    sorted[rank.clamp(1, sorted.len()) - 1]
+}
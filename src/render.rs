@@ -1,30 +1,45 @@
-use std::fmt;
-
 use crate::State;
+use crate::font::{BOLD, REGULAR, TextAlign, TextStyle, draw_text, draw_text_with};
+use crate::layout::LayoutOptions;
+use crate::math::Pair;
+use crate::selection::marquee_rect;
 use tiny_skia::{Color, FillRule, Paint, PathBuilder, Pixmap, Rect, Stroke, Transform};
 
+pub const LABEL_MAX_WIDTH: f32 = 160.0; // Logical pixels; longer labels end in an ellipsis
+const SELECTION_RING: f32 = 2.0; // Logical pixels
+
 pub fn draw_dots(pixmap: &mut Pixmap, state: &mut State) {
     let zoom = state.zoom.max(0.1);
     let mut paint = Paint::default();
     let min_mouse_distance = state
         .dots
+        .distances_to_cursor()
         .iter()
-        .map(|dot| dot.distance_to_cursor)
-        .min_by(|a, b| a.partial_cmp(b).unwrap())
+        .copied()
+        .min_by(f32::total_cmp)
         .unwrap_or(0.01);
 
-    for dot in &state.dots {
+    let alpha = state.time_info.interpolation;
+    let pick_radius = state.pick_radius();
+    let scale = state.scale_factor;
+    let viewport = Pair::new(pixmap.width() as f32, pixmap.height() as f32);
+    let transform = state.camera.transform(viewport, scale);
+    let base_radius = (3.0 * zoom) / 5.0;
+
+    let dots = &state.dots;
+    for id in dots.ids() {
+        let selected = state.selection.contains(id);
+        let position = dots.interpolated_position(id, alpha);
         let mut pb = PathBuilder::new();
-        let radius = (3.0 * zoom) / 5.0;
-        let width = pixmap.width();
-        let height = pixmap.height();
-        let x_offset = width as f32 / 2.0;
-        let y_offset = height as f32 / 2.0;
+        let radius = base_radius * dots.animated_radius_scale(id);
+        if radius <= 0.0 {
+            continue; // Just spawned
+        }
 
-        pb.push_circle(dot.position.x + x_offset, dot.position.y + y_offset, radius);
+        pb.push_circle(position.x, position.y, radius);
 
-        if dot.distance_to_cursor == min_mouse_distance && min_mouse_distance <= 30.0 {
-            if dot.is_selected {
+        if dots.distance_to_cursor(id) == min_mouse_distance && min_mouse_distance <= pick_radius {
+            if selected {
                 let color = Color::from_rgba8(107, 231, 72, 255);
                 paint.set_color(color);
             } else {
@@ -32,72 +47,129 @@ pub fn draw_dots(pixmap: &mut Pixmap, state: &mut State) {
                 paint.set_color(color);
             }
         } else {
-            paint.set_color(dot.color);
+            paint.set_color(dots.color(id));
         }
 
         if let Some(path) = pb.finish() {
-            pixmap.fill_path(
-                &path,
-                &paint,
-                FillRule::Winding,
-                Transform::identity(),
-                None,
-            );
+            pixmap.fill_path(&path, &paint, FillRule::Winding, transform, None);
+            if selected {
+                // A ring a constant 2 logical pixels wide, whatever the zoom
+                let stroke = Stroke {
+                    width: SELECTION_RING / state.camera.scale,
+                    ..Stroke::default()
+                };
+                paint.set_color_rgba8(255, 255, 255, 220);
+                pixmap.stroke_path(&path, &paint, &stroke, transform, None);
+            }
         } else {
             println!(
-                "Failed to create path! \ncenter: x={:?}+{:?},     y={:?}+{:?} \nradius: {:?}",
-                dot.position.x, x_offset, dot.position.y, y_offset, radius
+                "Failed to create path! \ncenter: x={:?},     y={:?} \nradius: {:?}",
+                position.x, position.y, radius
             );
         }
-        crate::font::draw_text(
+    }
+}
+
+// Labels go in their own pass, on top of every dot and timed separately
+pub fn draw_labels(pixmap: &mut Pixmap, state: &mut State) {
+    let zoom = state.zoom.max(0.1);
+    let alpha = state.time_info.interpolation;
+    let scale = state.scale_factor;
+    let viewport = Pair::new(pixmap.width() as f32, pixmap.height() as f32);
+    let base_radius = (3.0 * zoom) / 5.0;
+    let x_offset = viewport.x / 2.0;
+    let y_offset = viewport.y / 2.0;
+    let options = LayoutOptions::truncate((LABEL_MAX_WIDTH * scale).into());
+    let font_size = 14.0 * scale;
+    // How far past the edges an anchor can be with part of its label showing
+    let reach = Pair::new(LABEL_MAX_WIDTH * scale / 2.0, font_size * 2.0);
+
+    let off_screen = |anchor: Pair| {
+        anchor.x < -reach.x
+            || anchor.y < -reach.y
+            || anchor.x > viewport.x + reach.x
+            || anchor.y > viewport.y + reach.y
+    };
+
+    // Each directory's name at the center its files orbit. Scene clusters
+    // have no path, so no name.
+    for cluster in &state.clusters {
+        let anchor = state
+            .camera
+            .world_to_screen(cluster.position, viewport, scale);
+        if cluster.label.is_empty() || off_screen(anchor) {
+            continue;
+        }
+        draw_text_with(
             pixmap,
             &mut state.glyph_cache,
-            &state.font,
-            &dot.label,
-            (dot.position.x + x_offset).into(),
-            (dot.position.y + y_offset + radius + 10.0).into(),
-            (14.0).into(),
-            crate::font::TextAlign::Center,
+            &state.fonts,
+            &cluster.label,
+            (anchor.x.into(), anchor.y.into()),
+            TextStyle::new(BOLD, (font_size * 1.25).into(), TextAlign::Center),
+            &options,
         );
-        if let Some(focus_color) = state.focus_color {
-            let r = focus_color.red() * 255 as f32;
-            let g = focus_color.green() * 255 as f32;
-            let b = focus_color.blue() * 255 as f32;
-            let red = format!("Red: {:?}", r);
-            let green = format!("Green: {:?}", g);
-            let blue = format!("Blue: {:?}", b);
-
-            crate::font::draw_text(
-                pixmap,
-                &mut state.glyph_cache,
-                &state.font,
-                &red,
-                (x_offset).into(),
-                (y_offset - 40.0).into(),
-                (20.0).into(),
-                crate::font::TextAlign::Center,
-            );
-            crate::font::draw_text(
-                pixmap,
-                &mut state.glyph_cache,
-                &state.font,
-                &green,
-                (x_offset).into(),
-                (y_offset).into(),
-                (20.0).into(),
-                crate::font::TextAlign::Center,
-            );
-            crate::font::draw_text(
-                pixmap,
-                &mut state.glyph_cache,
-                &state.font,
-                &blue,
-                (x_offset).into(),
-                (y_offset + 40.0).into(),
-                (20.0).into(),
-                crate::font::TextAlign::Center,
-            );
+    }
+
+    let dots = &state.dots;
+    for id in dots.ids() {
+        let radius = base_radius * dots.animated_radius_scale(id);
+        if radius <= 0.0 {
+            continue; // Just spawned
+        }
+        let position = dots.interpolated_position(id, alpha);
+        let screen = state.camera.world_to_screen(position, viewport, scale);
+        let screen_radius = radius * state.camera.scale * scale;
+        let anchor = Pair::new(screen.x, screen.y + screen_radius + 10.0 * scale);
+        if off_screen(anchor) {
+            continue; // Off screen, so not worth laying out
         }
+        draw_text_with(
+            pixmap,
+            &mut state.glyph_cache,
+            &state.fonts,
+            dots.label(id),
+            (anchor.x.into(), anchor.y.into()),
+            TextStyle::new(REGULAR, font_size.into(), TextAlign::Center),
+            &options,
+        );
+    }
+
+    if let Some(focus_color) = state.focus_color {
+        let r = focus_color.red() * 255.0;
+        let g = focus_color.green() * 255.0;
+        let b = focus_color.blue() * 255.0;
+        let red = format!("Red: {:?}", r);
+        let green = format!("Green: {:?}", g);
+        let blue = format!("Blue: {:?}", b);
+
+        draw_text(
+            pixmap,
+            &mut state.glyph_cache,
+            &state.fonts,
+            &red,
+            (x_offset).into(),
+            (y_offset - 40.0 * scale).into(),
+            TextStyle::new(BOLD, (20.0 * scale).into(), TextAlign::Center),
+        );
+        draw_text(
+            pixmap,
+            &mut state.glyph_cache,
+            &state.fonts,
+            &green,
+            (x_offset).into(),
+            (y_offset).into(),
+            TextStyle::new(BOLD, (20.0 * scale).into(), TextAlign::Center),
+        );
+        draw_text(
+            pixmap,
+            &mut state.glyph_cache,
+            &state.fonts,
+            &blue,
+            (x_offset).into(),
+            (y_offset + 40.0 * scale).into(),
+            TextStyle::new(BOLD, (20.0 * scale).into(), TextAlign::Center),
+        );
     }
 }
 
@@ -105,17 +177,21 @@ pub fn draw_background(pixmap: &mut Pixmap, state: &State) {
     let width = pixmap.width();
     let height = pixmap.height();
 
-    let tile_size: u32 = 30; // Scale tile size with zoom
+    let scale = state.scale_factor;
+    let tile_size = ((30.0 * scale).round() as i64).max(1); // Scale tile size with zoom
 
     let parallax_factor = 0.8 / state.zoom; // Background moves slower with zoom-in effect
-    let x_offset = ((width as f32 / 2.0) * parallax_factor) as u32;
-    let y_offset = ((height as f32 / 2.0) * parallax_factor) as u32;
-
-    for y in (0..height).step_by(tile_size as usize) {
-        for x in (0..width).step_by(tile_size as usize) {
-            let x_adj = x + x_offset;
-            let y_adj = y + y_offset;
-            let color = if (x_adj / tile_size + y_adj / tile_size) % 2 == 0 {
+    let pan = state.camera.center * (state.camera.scale * scale * 0.8); // Tiles trail the camera
+    let x_offset = ((width as f32 / 2.0) * parallax_factor + pan.x) as i64;
+    let y_offset = ((height as f32 / 2.0) * parallax_factor + pan.y) as i64;
+
+    let mut y = -y_offset.rem_euclid(tile_size);
+    while y < height as i64 {
+        let mut x = -x_offset.rem_euclid(tile_size);
+        while x < width as i64 {
+            let column = (x + x_offset).div_euclid(tile_size);
+            let row = (y + y_offset).div_euclid(tile_size);
+            let color = if (column + row).rem_euclid(2) == 0 {
                 Color::from_rgba8(69, 92, 47, 255) // Light color
             } else {
                 Color::from_rgba8(99, 143, 57, 255) // Dark color
@@ -127,11 +203,11 @@ pub fn draw_background(pixmap: &mut Pixmap, state: &State) {
             let rect =
                 Rect::from_xywh(x as f32, y as f32, tile_size as f32, tile_size as f32).unwrap();
             pixmap.fill_rect(rect, &paint, Transform::identity(), None);
+            x += tile_size;
         }
+        y += tile_size;
     }
 
-    // Define border thickness (in pixels, converted from inches assuming 96 DPI)
-    let border_thickness = 30.0; // Adjust as needed
     let border_color = Color::from_rgba8(157, 181, 72, 255); // Black color
     let mut border_paint = Paint::default();
     border_paint.set_color(border_color);
@@ -140,23 +216,42 @@ pub fn draw_background(pixmap: &mut Pixmap, state: &State) {
         ..Default::default()
     };
 
-    // Create border path
+    // Create border path around the world origin
+    let viewport = Pair::new(width as f32, height as f32);
+    let transform = state.camera.transform(viewport, scale);
     let mut pb = PathBuilder::new();
-    pb.push_circle(width as f32 / 2.0, height as f32 / 2.0, 175.0);
+    pb.push_circle(0.0, 0.0, 175.0);
     let path = pb.finish().unwrap();
 
     if let Some(focus_color) = state.focus_color {
         let mut fill_paint = Paint::default();
         fill_paint.set_color(focus_color);
-        pixmap.fill_path(
-            &path,
-            &fill_paint,
-            FillRule::Winding,
-            Transform::identity(),
-            None,
-        );
+        pixmap.fill_path(&path, &fill_paint, FillRule::Winding, transform, None);
     }
 
     // Stroke the border path
-    pixmap.stroke_path(&path, &border_paint, &stroke, Transform::identity(), None);
+    pixmap.stroke_path(&path, &border_paint, &stroke, transform, None);
+}
+
+// The rubber band while a box select is being dragged out
+pub fn draw_selection_box(pixmap: &mut Pixmap, state: &State) {
+    let Some(start) = state.selection.marquee else {
+        return;
+    };
+    let (min, max) = marquee_rect(start, state.mouse_info.mouse_position);
+    let Some(rect) = Rect::from_ltrb(min.x, min.y, max.x.max(min.x + 1.0), max.y.max(min.y + 1.0))
+    else {
+        return;
+    };
+    let mut paint = Paint::default();
+    paint.set_color_rgba8(120, 170, 255, 50);
+    pixmap.fill_rect(rect, &paint, Transform::identity(), None);
+
+    let stroke = Stroke {
+        width: state.scale_factor,
+        ..Stroke::default()
+    };
+    paint.set_color_rgba8(120, 170, 255, 220);
+    let path = PathBuilder::from_rect(rect);
+    pixmap.stroke_path(&path, &paint, &stroke, Transform::identity(), None);
 }
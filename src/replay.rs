@@ -0,0 +1,175 @@
+use crate::file::{self, FileError, FileKind};
+use crate::input::{self, InputEvent, Key};
+use crate::snapshot::Snapshot;
+use crate::watch::FsEvent;
+use crate::{State, tree, update_dots};
+use serde::{Deserialize, Serialize};
+use std::fs;
+use std::path::Path;
+
+pub const RECORDING_VERSION: u32 = 1; // See FileKind::version
+
+// What a session can be fed: input from the window, or a change the watcher
+// saw under --path
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(untagged)]
+pub enum SessionEvent {
+    Input(InputEvent),
+    Fs(FsEvent),
+}
+
+impl From<InputEvent> for SessionEvent {
+    fn from(event: InputEvent) -> Self {
+        SessionEvent::Input(event)
+    }
+}
+
+impl From<FsEvent> for SessionEvent {
+    fn from(event: FsEvent) -> Self {
+        SessionEvent::Fs(event)
+    }
+}
+
+// An event and the step it arrived after. The step is what makes a replay
+// exact: the event is applied between the same two update_dots calls however
+// the frames fell.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct RecordedEvent {
+    pub step: u64,
+    pub time: f64, // TimeInfo::input_time, which throws are timed by
+    #[serde(flatten)]
+    pub event: SessionEvent,
+    // For F9, the quick save it loaded, so replays don't depend on the file
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub quickload: Option<Snapshot>,
+}
+
+// A session: the simulation as it was when recording started, the window it
+// ran in, and every input event after that
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Recording {
+    pub version: u32,
+    pub width: u32, // Physical pixels
+    pub height: u32,
+    pub scale_factor: f32,
+    pub end_step: u64, // Step count when recording stopped
+    pub start: Snapshot,
+    pub events: Vec<RecordedEvent>,
+}
+
+impl Recording {
+    pub fn new(state: &State) -> Recording {
+        Recording {
+            version: RECORDING_VERSION,
+            width: state.window_size.width,
+            height: state.window_size.height,
+            scale_factor: state.scale_factor,
+            end_step: state.time_info.step_count,
+            start: Snapshot::capture(state),
+            events: Vec::new(),
+        }
+    }
+
+    // Call before applying the event, so it's tagged with the steps run so far
+    pub fn record(&mut self, state: &State, event: impl Into<SessionEvent>) {
+        let event = event.into();
+        let quickload = match event {
+            SessionEvent::Input(InputEvent::Key { key: Key::F9 }) => {
+                fs::read_to_string(&state.quick_save)
+                    .ok()
+                    .and_then(|text| Snapshot::from_toml(&text).ok())
+            }
+            _ => None,
+        };
+        self.events.push(RecordedEvent {
+            step: state.time_info.step_count,
+            time: state.time_info.input_time,
+            event,
+            quickload,
+        });
+        self.end_step = state.time_info.step_count;
+    }
+
+    pub fn finish(&mut self, state: &State) {
+        self.end_step = state.time_info.step_count;
+    }
+
+    pub fn save(&self, path: &Path) -> Result<(), FileError> {
+        let text = file::to_toml(FileKind::Recording, self)?;
+        file::write(FileKind::Recording, path, &text)
+    }
+
+    pub fn load(path: &Path) -> Result<Recording, FileError> {
+        let text = file::read(FileKind::Recording, path)?;
+        file::from_toml(FileKind::Recording, &text)
+    }
+}
+
+// Feeds a recording back into a state, each event after the step it was
+// recorded at
+pub struct Replay {
+    pub recording: Recording,
+    pub next: usize, // Index of the first event not yet applied
+}
+
+impl Replay {
+    pub fn new(recording: Recording) -> Replay {
+        Replay { recording, next: 0 }
+    }
+
+    // Puts `state` where the recording started
+    pub fn start(&mut self, state: &mut State) {
+        self.next = 0;
+        self.recording.start.restore(state);
+        state.resize(
+            self.recording.width,
+            self.recording.height,
+            self.recording.scale_factor,
+        );
+    }
+
+    // Applies every event due by the current step. Returns false if one of
+    // them was the user quitting. Keys that touch files don't: saves and the
+    // profile dump are skipped, and quick loads use the recorded snapshot.
+    pub fn apply_due(&mut self, state: &mut State) -> bool {
+        let mut running = true;
+        while let Some(recorded) = self.recording.events.get(self.next) {
+            if recorded.step > state.time_info.step_count {
+                break;
+            }
+            self.next += 1;
+            state.time_info.input_time = recorded.time;
+            match &recorded.event {
+                SessionEvent::Input(InputEvent::Key {
+                    key: Key::F4 | Key::F5,
+                }) => {}
+                SessionEvent::Input(InputEvent::Key { key: Key::F9 }) => {
+                    if let Some(snapshot) = &recorded.quickload {
+                        snapshot.restore(state);
+                    }
+                }
+                SessionEvent::Input(event) => running &= input::apply(state, event),
+                SessionEvent::Fs(event) => tree::apply_fs_event(state, event),
+            }
+        }
+        running
+    }
+
+    pub fn is_finished(&self, state: &State) -> bool {
+        self.next == self.recording.events.len()
+            && state.time_info.step_count >= self.recording.end_step
+    }
+
+    // Replays the whole recording back to back, as headless::step does
+    pub fn run(&mut self, state: &mut State) {
+        loop {
+            self.apply_due(state);
+            if self.is_finished(state) {
+                break;
+            }
+            update_dots(state);
+            state.time_info.frame_count += 1;
+        }
+        state.time_info.interpolation = 1.0;
+    }
+}
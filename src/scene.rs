@@ -0,0 +1,425 @@
+use crate::bounds::{BoundsArea, DEFAULT_DAMPING, DEFAULT_MARGIN, EdgeBehavior};
+use crate::forces::Forces;
+use crate::integrate::Integrator;
+use crate::math::Pair;
+use crate::sph::{DEFAULT_NEAR_PRESSURE_MULTIPLIER, DEFAULT_PARTICLE_MASS, DEFAULT_VISCOSITY};
+use crate::tree::Cluster;
+use crate::{
+    CENTER_REPULSIVE_RADIUS, DEFAULT_FIXED_STEP, DEFAULT_MAX_SUBSTEPS, DEFAULT_MAX_THROW_SPEED,
+    Dot, PARTICLE_REPULSIVE_RADIUS, SMOOTHING_RADIUS, State,
+};
+use rand::Rng;
+use serde::Deserialize;
+use std::fmt;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+use tiny_skia::Color;
+
+pub const MAX_SCENE_DOTS: usize = 1_000_000;
+
+// Everything needed to start a simulation, as read from a TOML file. Every
+// section and field is optional; see scenes/ for examples.
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct Scene {
+    pub window: WindowSettings,
+    pub sim: SimSettings,
+    pub spawn: Vec<SpawnGroup>, // No groups means one default group
+}
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct WindowSettings {
+    pub width: u32,
+    pub height: u32,
+    pub title: String,
+}
+
+impl Default for WindowSettings {
+    fn default() -> Self {
+        WindowSettings {
+            width: 800,
+            height: 600,
+            title: String::from("tiny-skia Animation"),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct SimSettings {
+    pub zoom: f32,
+    pub target_density: f32,
+    pub pressure_multiplier: f32,
+    pub speed_scale: Option<f32>, // Defaults to 1 / number of dots
+    pub force_scale: Option<f32>, // Defaults to 1 / number of dots
+    pub smoothing_radius: f32,    // The SPH kernel radius
+    pub particle_mass: f32,
+    pub near_pressure_multiplier: f32,
+    pub viscosity: f32,
+    pub center_repulsive_radius: f32,
+    pub particle_repulsive_radius: f32,
+    pub max_throw_speed: f32,
+    pub damping: f32,
+    pub margin: f32,         // Unused with bounds.world
+    pub edges: EdgeBehavior, // Every edge bounds doesn't set
+    pub bounds: BoundsSettings,
+    pub fixed_step: f32, // Real seconds per step; smaller runs the sim faster
+    pub max_substeps: u32,
+    pub integrator: Integrator,
+    pub disabled_forces: Vec<String>, // Built-in forces to switch off, by name
+    pub seed: Option<u64>,            // Random unless set here or with --seed
+}
+
+impl Default for SimSettings {
+    fn default() -> Self {
+        SimSettings {
+            zoom: 40.0,
+            target_density: 0.05,
+            pressure_multiplier: 10.0,
+            speed_scale: None,
+            force_scale: None,
+            smoothing_radius: SMOOTHING_RADIUS,
+            particle_mass: DEFAULT_PARTICLE_MASS,
+            near_pressure_multiplier: DEFAULT_NEAR_PRESSURE_MULTIPLIER,
+            viscosity: DEFAULT_VISCOSITY,
+            center_repulsive_radius: CENTER_REPULSIVE_RADIUS,
+            particle_repulsive_radius: PARTICLE_REPULSIVE_RADIUS,
+            max_throw_speed: DEFAULT_MAX_THROW_SPEED,
+            damping: DEFAULT_DAMPING,
+            margin: DEFAULT_MARGIN,
+            edges: EdgeBehavior::Reflect,
+            bounds: BoundsSettings::default(),
+            fixed_step: DEFAULT_FIXED_STEP,
+            max_substeps: DEFAULT_MAX_SUBSTEPS,
+            integrator: Integrator::default(),
+            disabled_forces: Vec::new(),
+            seed: None,
+        }
+    }
+}
+
+// [sim.bounds]: per-edge behaviors, and optionally a fixed rect in the world
+// instead of the window
+#[derive(Debug, Clone, Copy, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct BoundsSettings {
+    pub left: Option<EdgeBehavior>, // Each defaults to sim.edges
+    pub right: Option<EdgeBehavior>,
+    pub top: Option<EdgeBehavior>,
+    pub bottom: Option<EdgeBehavior>,
+    pub world: Option<WorldRect>,
+}
+
+// A rect in world units; x and y are its top-left corner
+#[derive(Debug, Clone, Copy, PartialEq, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct WorldRect {
+    pub x: f32,
+    pub y: f32,
+    pub width: f32,
+    pub height: f32,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Distribution {
+    Ring, // On the circle of `radius`
+    Disc, // Anywhere inside it
+}
+
+// A batch of dots orbiting `center`. Each group gets its own cluster.
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct SpawnGroup {
+    pub count: usize,
+    pub distribution: Distribution,
+    pub center: [f32; 2],
+    pub radius: f32,
+    pub color: String,    // "#rrggbb"
+    pub color_jitter: u8, // Each channel varies by up to this much either way
+    pub label: Option<String>,
+}
+
+impl Default for SpawnGroup {
+    fn default() -> Self {
+        SpawnGroup {
+            count: 30,
+            distribution: Distribution::Ring,
+            center: [0.0, 0.0],
+            radius: 150.0,
+            color: String::from("#cf1f48"),
+            color_jitter: 30,
+            label: None,
+        }
+    }
+}
+
+impl SpawnGroup {
+    // Dots moving around the group's center, fast enough for a stable orbit.
+    // `cluster` is the index of the cluster they should orbit.
+    pub fn generate(&self, cluster: usize, rng: &mut impl Rng) -> Result<Vec<Dot>, SceneError> {
+        let base = parse_color(&self.color).ok_or_else(|| SceneError::Invalid {
+            field: String::from("spawn.color"),
+            message: format!("'{}' is not a #rrggbb color", self.color),
+        })?;
+        let center = Pair::new(self.center[0], self.center[1]);
+        let speed = (self.radius / (self.count as f32 * 10.0)).sqrt() * 0.1;
+        let jitter = self.color_jitter as i32;
+
+        let dots = (0..self.count)
+            .map(|_| {
+                let angle = rng.gen_range(1.0..3.0 * std::f32::consts::PI);
+                let distance = match self.distribution {
+                    Distribution::Ring => self.radius,
+                    Distribution::Disc => self.radius * rng.gen_range(0.0f32..1.0).sqrt(),
+                };
+                let position = center + Pair::new(angle.cos(), angle.sin()) * distance;
+                let mut channel = |value: u8| {
+                    let offset = if jitter > 0 {
+                        rng.gen_range(-jitter..jitter)
+                    } else {
+                        0
+                    };
+                    (value as i32 + offset).clamp(0, 255) as u8
+                };
+                let color =
+                    Color::from_rgba8(channel(base[0]), channel(base[1]), channel(base[2]), 255);
+                let velocity = Pair::new(-speed / 2.0 * angle.sin(), speed / 2.5 * angle.cos());
+
+                let mut dot = Dot::new(position, velocity, 0.0, color);
+                if let Some(label) = &self.label {
+                    dot.label = label.clone();
+                }
+                dot.cluster = cluster;
+                dot
+            })
+            .collect();
+        Ok(dots)
+    }
+}
+
+#[derive(Debug)]
+pub enum SceneError {
+    Io { path: PathBuf, source: io::Error },
+    Parse { path: PathBuf, message: String },
+    Invalid { field: String, message: String },
+}
+
+impl fmt::Display for SceneError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            SceneError::Io { path, source } => {
+                write!(f, "Can't read scene {}: {source}", path.display())
+            }
+            SceneError::Parse { path, message } => {
+                write!(f, "Invalid scene {}: {message}", path.display())
+            }
+            SceneError::Invalid { field, message } => write!(f, "Invalid scene {field}: {message}"),
+        }
+    }
+}
+
+impl std::error::Error for SceneError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            SceneError::Io { source, .. } => Some(source),
+            _ => None,
+        }
+    }
+}
+
+impl Scene {
+    pub fn load(path: &Path) -> Result<Scene, SceneError> {
+        let text = fs::read_to_string(path).map_err(|source| SceneError::Io {
+            path: path.to_path_buf(),
+            source,
+        })?;
+        let scene: Scene = toml::from_str(&text).map_err(|err| SceneError::Parse {
+            path: path.to_path_buf(),
+            message: err.to_string(),
+        })?;
+        scene.validate()?;
+        Ok(scene)
+    }
+
+    // Catches values that parse fine but would break the simulation
+    pub fn validate(&self) -> Result<(), SceneError> {
+        let invalid = |field: &str, message: &str| {
+            Err(SceneError::Invalid {
+                field: field.to_string(),
+                message: message.to_string(),
+            })
+        };
+        let sim = &self.sim;
+
+        if self.window.width == 0 || self.window.height == 0 {
+            return invalid("window", "width and height must be at least 1");
+        }
+        let positive = [
+            ("sim.zoom", sim.zoom),
+            ("sim.smoothing_radius", sim.smoothing_radius),
+            ("sim.particle_mass", sim.particle_mass),
+            ("sim.fixed_step", sim.fixed_step),
+        ];
+        for (field, value) in positive {
+            if !(value.is_finite() && value > 0.0) {
+                return invalid(field, "must be a number above 0");
+            }
+        }
+        let non_negative = [
+            ("sim.target_density", sim.target_density),
+            ("sim.pressure_multiplier", sim.pressure_multiplier),
+            ("sim.near_pressure_multiplier", sim.near_pressure_multiplier),
+            ("sim.center_repulsive_radius", sim.center_repulsive_radius),
+            (
+                "sim.particle_repulsive_radius",
+                sim.particle_repulsive_radius,
+            ),
+            ("sim.max_throw_speed", sim.max_throw_speed),
+            ("sim.margin", sim.margin),
+            ("sim.speed_scale", sim.speed_scale.unwrap_or(0.0)),
+            ("sim.force_scale", sim.force_scale.unwrap_or(0.0)),
+        ];
+        for (field, value) in non_negative {
+            if !(value.is_finite() && value >= 0.0) {
+                return invalid(field, "must be a number of at least 0");
+            }
+        }
+        if !(0.0..=1.0).contains(&sim.viscosity) {
+            return invalid("sim.viscosity", "must be between 0 and 1");
+        }
+        if !(0.0..=1.0).contains(&sim.damping) {
+            return invalid("sim.damping", "must be between 0 and 1");
+        }
+        if sim.max_substeps == 0 {
+            return invalid("sim.max_substeps", "must be at least 1");
+        }
+        if let Some(world) = sim.bounds.world {
+            if !(world.x.is_finite() && world.y.is_finite()) {
+                return invalid("sim.bounds.world", "x and y must be finite numbers");
+            }
+            let (width, height) = (world.width, world.height);
+            if !(width.is_finite() && width > 0.0 && height.is_finite() && height > 0.0) {
+                return invalid(
+                    "sim.bounds.world",
+                    "width and height must be numbers above 0",
+                );
+            }
+        }
+        let builtin = Forces::builtin();
+        for name in &sim.disabled_forces {
+            if builtin.position(name).is_none() {
+                let names: Vec<_> = builtin.names().collect();
+                return invalid(
+                    "sim.disabled_forces",
+                    &format!("'{name}' is not one of {}", names.join(", ")),
+                );
+            }
+        }
+
+        let mut total = 0;
+        for group in &self.spawn {
+            total += group.count;
+            if !(group.radius.is_finite() && group.radius >= 0.0) {
+                return invalid("spawn.radius", "must be a number of at least 0");
+            }
+            if !group.center.iter().all(|c| c.is_finite()) {
+                return invalid("spawn.center", "must be two finite numbers");
+            }
+            if parse_color(&group.color).is_none() {
+                return invalid(
+                    "spawn.color",
+                    &format!("'{}' is not a #rrggbb color", group.color),
+                );
+            }
+        }
+        if total > MAX_SCENE_DOTS {
+            return invalid(
+                "spawn",
+                &format!("{total} dots is more than the limit of {MAX_SCENE_DOTS}"),
+            );
+        }
+        Ok(())
+    }
+
+    pub fn build_state(
+        &self,
+        window_width: u32,
+        window_height: u32,
+        seed: u64,
+    ) -> Result<State, SceneError> {
+        let mut state = State::with_seed(0, window_width, window_height, seed);
+        let default_groups = [SpawnGroup::default()];
+        let groups = if self.spawn.is_empty() {
+            &default_groups[..]
+        } else {
+            &self.spawn[..]
+        };
+
+        state.clusters.clear();
+        for (index, group) in groups.iter().enumerate() {
+            let center = Pair::new(group.center[0], group.center[1]);
+            state.clusters.push(Cluster::new(center, PathBuf::new(), 0));
+            state.dots.extend(group.generate(index, &mut state.rng)?);
+        }
+
+        self.apply_sim(&mut state);
+        Ok(state)
+    }
+
+    // Sets the tunables without touching the dots, e.g. for a --path tree
+    pub fn apply_sim(&self, state: &mut State) {
+        let sim = &self.sim;
+        let ndots = state.dots.len().max(1) as f32;
+        state.zoom = sim.zoom;
+        state.target_density = sim.target_density;
+        state.pressure_multiplier = sim.pressure_multiplier;
+        state.speed_scale = sim.speed_scale.unwrap_or(1.0 / ndots);
+        state.force_scale = sim.force_scale.unwrap_or(1.0 / ndots);
+        state.smoothing_radius = sim.smoothing_radius;
+        state.particle_mass = sim.particle_mass;
+        state.near_pressure_multiplier = sim.near_pressure_multiplier;
+        state.viscosity = sim.viscosity;
+        state.center_repulsive_radius = sim.center_repulsive_radius;
+        state.particle_repulsive_radius = sim.particle_repulsive_radius;
+        state.max_throw_speed = sim.max_throw_speed;
+        state.bounds.damping = sim.damping;
+        let bounds = &mut state.bounds;
+        bounds.left = sim.bounds.left.unwrap_or(sim.edges);
+        bounds.right = sim.bounds.right.unwrap_or(sim.edges);
+        bounds.top = sim.bounds.top.unwrap_or(sim.edges);
+        bounds.bottom = sim.bounds.bottom.unwrap_or(sim.edges);
+        match (sim.bounds.world, &mut bounds.area) {
+            (Some(world), area) => {
+                *area = BoundsArea::World {
+                    min: Pair::new(world.x, world.y),
+                    max: Pair::new(world.x + world.width, world.y + world.height),
+                };
+            }
+            (None, BoundsArea::Window { margin }) => *margin = sim.margin,
+            (None, BoundsArea::World { .. }) => {} // Fitted around a --path tree
+        }
+        state.time_info.fixed_step = sim.fixed_step;
+        state.time_info.max_substeps = sim.max_substeps;
+        state.integrator = sim.integrator;
+        state.momentum.reset();
+        for slot in &mut state.forces.slots {
+            slot.enabled = !sim
+                .disabled_forces
+                .iter()
+                .any(|name| name == slot.force.name());
+        }
+    }
+}
+
+// "#rrggbb" or "rrggbb"
+pub fn parse_color(text: &str) -> Option<[u8; 3]> {
+    let hex = text.strip_prefix('#').unwrap_or(text);
+    if hex.len() != 6 || !hex.is_ascii() {
+        return None;
+    }
+    let channel = |i: usize| u8::from_str_radix(&hex[i..i + 2], 16).ok();
+    Some([channel(0)?, channel(2)?, channel(4)?])
+}
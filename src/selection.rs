@@ -0,0 +1,80 @@
+use crate::math::Pair;
+use crate::particles::{DotId, Particles};
+use std::collections::BTreeSet;
+
+// Which dots are selected, plus any drag or box
+// select under way. Ordered so anything walking it does so the same way
+// every run.
+#[derive(Debug, Clone, Default)]
+pub struct Selection {
+    pub dots: BTreeSet<DotId>,
+    pub grab: Option<Pair>,    // World point the group is being dragged by
+    pub marquee: Option<Pair>, // Screen point (physical pixels) a box select started at
+}
+
+impl Selection {
+    pub fn new() -> Self {
+        Selection::default()
+    }
+
+    pub fn contains(&self, id: DotId) -> bool {
+        self.dots.contains(&id)
+    }
+
+    pub fn len(&self) -> usize {
+        self.dots.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.dots.is_empty()
+    }
+
+    pub fn insert(&mut self, id: DotId) {
+        self.dots.insert(id);
+    }
+
+    // Adds the dot if it wasn't selected, removes it if it was
+    pub fn toggle(&mut self, id: DotId) {
+        if !self.dots.remove(&id) {
+            self.dots.insert(id);
+        }
+    }
+
+    pub fn clear(&mut self) {
+        self.dots.clear();
+    }
+
+    pub fn select_all(&mut self, dots: &Particles) {
+        self.dots = dots.ids().collect();
+    }
+
+    pub fn invert(&mut self, dots: &Particles) {
+        self.dots = dots.ids().filter(|id| !self.dots.contains(id)).collect();
+    }
+
+    // Follows a removal of dots, where `kept[i]` says whether the old dot `i`
+    // survived and `dots` is the store after it. Removed dots drop out and
+    // the rest get their new handles.
+    pub fn retain(&mut self, kept: &[bool], dots: &Particles) {
+        let mut new_index = Vec::with_capacity(kept.len());
+        let mut next = 0;
+        for &keep in kept {
+            new_index.push(keep.then_some(next));
+            next += keep as usize;
+        }
+        self.dots = self
+            .dots
+            .iter()
+            .filter_map(|&id| new_index.get(id.index()).copied().flatten())
+            .filter_map(|index| dots.id(index))
+            .collect();
+    }
+}
+
+// Top-left and bottom-right of the box between two corners
+pub fn marquee_rect(start: Pair, end: Pair) -> (Pair, Pair) {
+    (
+        Pair::new(start.x.min(end.x), start.y.min(end.y)),
+        Pair::new(start.x.max(end.x), start.y.max(end.y)),
+    )
+}
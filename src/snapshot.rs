@@ -0,0 +1,265 @@
+use crate::bounds::Bounds;
+use crate::camera::Camera;
+use crate::file::{self, FileError, FileKind};
+use crate::integrate::Integrator;
+use crate::math::Pair;
+use crate::selection::Selection;
+use crate::tree::Cluster;
+use crate::{Dot, State};
+use rand_chacha::ChaCha8Rng;
+use rand_chacha::rand_core::SeedableRng;
+use serde::{Deserialize, Serialize};
+use std::path::{Path, PathBuf};
+use std::time::{Duration, Instant};
+use tiny_skia::Color;
+
+pub const SNAPSHOT_VERSION: u32 = 1; // See FileKind::version
+
+// Everything about a running simulation worth keeping, in a layout of its own
+// so State can change without breaking old files. Window size, fonts, caches
+// and the overlays aren't part of it.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Snapshot {
+    pub version: u32,
+    pub seed: u64,
+    pub rng_position: u64, // Words the rng had used, so spawning carries on where it was
+    pub selection: Vec<usize>, // Indices into dots
+    pub disabled_forces: Vec<String>, // By name
+    pub tunables: Tunables,
+    pub time: TimeSnapshot,
+    pub camera: Camera,
+    pub bounds: Bounds,
+    pub clusters: Vec<ClusterSnapshot>,
+    pub dots: Vec<DotSnapshot>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Tunables {
+    pub zoom: f32,
+    pub target_density: f32,
+    pub pressure_multiplier: f32,
+    pub speed_scale: f32,
+    pub force_scale: f32,
+    pub smoothing_radius: f32,
+    pub particle_mass: f32,
+    pub near_pressure_multiplier: f32,
+    pub viscosity: f32,
+    pub center_repulsive_radius: f32,
+    pub particle_repulsive_radius: f32,
+    pub max_throw_speed: f32,
+    pub integrator: Integrator,
+    pub focus_color: Option<[f32; 4]>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct TimeSnapshot {
+    pub uptime: f32, // Seconds since start
+    pub frame_count: u64,
+    pub fixed_step: f32,
+    pub max_substeps: u32,
+    pub accumulator: f32,
+    pub step_count: u64,
+    pub interpolation: f32,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ClusterSnapshot {
+    pub position: Pair,
+    pub path: PathBuf,
+    pub depth: usize,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct DotSnapshot {
+    pub position: Pair,
+    pub previous_position: Pair,
+    pub velocity: Pair,
+    pub density: f32,
+    pub color: [f32; 4],
+    pub label: String,
+    pub radius_scale: f32,
+    pub cluster: usize,
+    pub path: Option<PathBuf>,
+    pub spawn_progress: f32,
+    pub pulse: f32,
+}
+
+impl Snapshot {
+    pub fn capture(state: &State) -> Snapshot {
+        let time = &state.time_info;
+        Snapshot {
+            version: SNAPSHOT_VERSION,
+            seed: state.seed,
+            rng_position: state.rng.get_word_pos() as u64,
+            selection: state.selection.dots.iter().map(|id| id.index()).collect(),
+            disabled_forces: state
+                .forces
+                .slots
+                .iter()
+                .filter(|slot| !slot.enabled)
+                .map(|slot| slot.force.name().to_string())
+                .collect(),
+            tunables: Tunables {
+                zoom: state.zoom,
+                target_density: state.target_density,
+                pressure_multiplier: state.pressure_multiplier,
+                speed_scale: state.speed_scale,
+                force_scale: state.force_scale,
+                smoothing_radius: state.smoothing_radius,
+                particle_mass: state.particle_mass,
+                near_pressure_multiplier: state.near_pressure_multiplier,
+                viscosity: state.viscosity,
+                center_repulsive_radius: state.center_repulsive_radius,
+                particle_repulsive_radius: state.particle_repulsive_radius,
+                max_throw_speed: state.max_throw_speed,
+                integrator: state.integrator,
+                focus_color: state.focus_color.map(color_to_array),
+            },
+            time: TimeSnapshot {
+                uptime: time.start.elapsed().as_secs_f32(),
+                frame_count: time.frame_count,
+                fixed_step: time.fixed_step,
+                max_substeps: time.max_substeps,
+                accumulator: time.accumulator,
+                step_count: time.step_count,
+                interpolation: time.interpolation,
+            },
+            camera: state.camera,
+            bounds: state.bounds,
+            clusters: state
+                .clusters
+                .iter()
+                .map(|cluster| ClusterSnapshot {
+                    position: cluster.position,
+                    path: cluster.path.clone(),
+                    depth: cluster.depth,
+                })
+                .collect(),
+            dots: state
+                .dots
+                .ids()
+                .map(|id| DotSnapshot::from(&state.dots.get(id)))
+                .collect(),
+        }
+    }
+
+    // Puts the saved simulation into `state`, keeping its window and overlays
+    pub fn restore(&self, state: &mut State) {
+        let tunables = &self.tunables;
+        state.zoom = tunables.zoom;
+        state.target_density = tunables.target_density;
+        state.pressure_multiplier = tunables.pressure_multiplier;
+        state.speed_scale = tunables.speed_scale;
+        state.force_scale = tunables.force_scale;
+        state.smoothing_radius = tunables.smoothing_radius;
+        state.particle_mass = tunables.particle_mass;
+        state.near_pressure_multiplier = tunables.near_pressure_multiplier;
+        state.viscosity = tunables.viscosity;
+        state.center_repulsive_radius = tunables.center_repulsive_radius;
+        state.particle_repulsive_radius = tunables.particle_repulsive_radius;
+        state.max_throw_speed = tunables.max_throw_speed;
+        state.integrator = tunables.integrator;
+        state.momentum.reset();
+        state.focus_color = tunables.focus_color.and_then(array_to_color);
+
+        let time = &mut state.time_info;
+        let now = Instant::now();
+        time.start = now
+            .checked_sub(Duration::from_secs_f32(self.time.uptime.max(0.0)))
+            .unwrap_or(now);
+        time.last_frame_time = now;
+        time.frame_count = self.time.frame_count;
+        time.fixed_step = self.time.fixed_step;
+        time.max_substeps = self.time.max_substeps;
+        time.accumulator = self.time.accumulator;
+        time.step_count = self.time.step_count;
+        time.interpolation = self.time.interpolation;
+
+        state.camera = self.camera;
+        state.bounds = self.bounds;
+        state.clusters = self
+            .clusters
+            .iter()
+            .map(|cluster| Cluster::new(cluster.position, cluster.path.clone(), cluster.depth))
+            .collect();
+        state.dots = self.dots.iter().map(Dot::from).collect();
+        state.selection = Selection::new();
+        state.selection.dots = self
+            .selection
+            .iter()
+            .filter_map(|&index| state.dots.id(index))
+            .collect();
+        // Forces are code, so only which ones were off is saved. Any added
+        // since keep their current setting.
+        for slot in &mut state.forces.slots {
+            slot.enabled = !self
+                .disabled_forces
+                .iter()
+                .any(|name| name == slot.force.name());
+        }
+
+        state.seed = self.seed;
+        state.rng = ChaCha8Rng::seed_from_u64(self.seed);
+        state.rng.set_word_pos(self.rng_position as u128);
+    }
+
+    pub fn to_toml(&self) -> Result<String, FileError> {
+        file::to_toml(FileKind::Snapshot, self)
+    }
+
+    pub fn from_toml(text: &str) -> Result<Snapshot, FileError> {
+        file::from_toml(FileKind::Snapshot, text)
+    }
+}
+
+impl From<&Dot> for DotSnapshot {
+    fn from(dot: &Dot) -> Self {
+        DotSnapshot {
+            position: dot.position,
+            previous_position: dot.previous_position,
+            velocity: dot.velocity,
+            density: dot.density,
+            color: color_to_array(dot.color),
+            label: dot.label.clone(),
+            radius_scale: dot.radius_scale,
+            cluster: dot.cluster,
+            path: dot.path.clone(),
+            spawn_progress: dot.spawn_progress,
+            pulse: dot.pulse,
+        }
+    }
+}
+
+impl From<&DotSnapshot> for Dot {
+    fn from(saved: &DotSnapshot) -> Self {
+        let color = array_to_color(saved.color).unwrap_or(Color::WHITE);
+        let mut dot = Dot::new(saved.position, saved.velocity, saved.density, color);
+        dot.previous_position = saved.previous_position;
+        dot.label = saved.label.clone();
+        dot.radius_scale = saved.radius_scale;
+        dot.cluster = saved.cluster;
+        dot.path = saved.path.clone();
+        dot.spawn_progress = saved.spawn_progress;
+        dot.pulse = saved.pulse;
+        dot
+    }
+}
+
+fn color_to_array(color: Color) -> [f32; 4] {
+    [color.red(), color.green(), color.blue(), color.alpha()]
+}
+
+fn array_to_color([r, g, b, a]: [f32; 4]) -> Option<Color> {
+    Color::from_rgba(r, g, b, a)
+}
+
+pub fn save(state: &State, path: &Path) -> Result<(), FileError> {
+    let text = Snapshot::capture(state).to_toml()?;
+    file::write(FileKind::Snapshot, path, &text)
+}
+
+pub fn load(state: &mut State, path: &Path) -> Result<(), FileError> {
+    let text = file::read(FileKind::Snapshot, path)?;
+    Snapshot::from_toml(&text)?.restore(state);
+    Ok(())
+}
@@ -0,0 +1,184 @@
+use crate::grid::SpatialGrid;
+use crate::math::Pair;
+use crate::{State, parallel};
+use std::f32::consts::PI;
+
+pub const DEFAULT_PARTICLE_MASS: f32 = 1.0 / 2000.0;
+pub const DEFAULT_NEAR_PRESSURE_MULTIPLIER: f32 = 50000.0;
+pub const DEFAULT_VISCOSITY: f32 = 0.05;
+
+// The fluid settings one update reads, copied out of State so the passes can
+// run while the dots are being changed
+#[derive(Debug, Clone, Copy)]
+pub struct SphParams {
+    pub kernel_radius: f32, // State::smoothing_radius
+    pub particle_mass: f32,
+    pub target_density: f32,
+    pub pressure_multiplier: f32,
+    pub near_pressure_multiplier: f32,
+    pub viscosity: f32, // Fraction of the velocity difference to neighbors removed per step
+}
+
+impl SphParams {
+    pub fn from_state(state: &State) -> SphParams {
+        SphParams {
+            kernel_radius: state.smoothing_radius,
+            particle_mass: state.particle_mass,
+            target_density: state.target_density,
+            pressure_multiplier: state.pressure_multiplier,
+            near_pressure_multiplier: state.near_pressure_multiplier,
+            viscosity: state.viscosity,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct Densities {
+    pub density: f32,
+    pub near: f32, // From the spikier near kernel; only ever pushes dots apart
+}
+
+// 2D poly6 kernel, normalized to integrate to 1
+pub fn density_kernel(radius: f32, distance: f32) -> f32 {
+    let volume = PI * radius.powi(8) / 4.0;
+    let smoothing = (radius * radius - distance * distance).max(0.0);
+    smoothing * smoothing * smoothing / volume
+}
+
+pub fn density_kernel_slope(radius: f32, distance: f32) -> f32 {
+    if distance >= radius {
+        return 0.0;
+    }
+    let f = radius * radius - distance * distance;
+    let scale = -24.0 / (PI * radius.powi(8));
+    scale * distance * f * f
+}
+
+// 2D spiky kernel, (r - d)^3, normalized. Unlike poly6 its slope doesn't
+// flatten out near zero, so it keeps close dots apart.
+pub fn near_density_kernel(radius: f32, distance: f32) -> f32 {
+    let v = (radius - distance).max(0.0);
+    v * v * v * 10.0 / (PI * radius.powi(5))
+}
+
+pub fn near_density_kernel_slope(radius: f32, distance: f32) -> f32 {
+    let v = (radius - distance).max(0.0);
+    -v * v * 30.0 / (PI * radius.powi(5))
+}
+
+pub fn density_to_pressure(density: f32, target_density: f32, pressure_multiplier: f32) -> f32 {
+    let density_error = density - target_density;
+    density_error * pressure_multiplier
+}
+
+// Each dot's densities into `densities`, replacing what it held
+pub fn compute_densities(
+    positions: &[Pair],
+    grid: &SpatialGrid,
+    params: &SphParams,
+    densities: &mut Vec<Densities>,
+) {
+    let radius = params.kernel_radius;
+    parallel::map_into(densities, positions.len(), |i| {
+        let position = positions[i];
+        grid.query_radius(position, radius)
+            .map(|j| {
+                let distance = position.distance(positions[j]);
+                Densities {
+                    density: params.particle_mass * density_kernel(radius, distance),
+                    near: params.particle_mass * near_density_kernel(radius, distance),
+                }
+            })
+            .fold(Densities::default(), |sum, d| Densities {
+                density: sum.density + d.density,
+                near: sum.near + d.near,
+            })
+    });
+}
+
+// Pressure and near-pressure push on dot `i`. Each pair uses the average of
+// both dots' pressures over the average of their densities, so dot i pushes
+// on j exactly as hard as j pushes on i.
+pub fn pressure_force(
+    i: usize,
+    positions: &[Pair],
+    densities: &[Densities],
+    grid: &SpatialGrid,
+    params: &SphParams,
+) -> Pair {
+    let radius = params.kernel_radius;
+    let position = positions[i];
+    let own = densities[i];
+    let own_pressure = density_to_pressure(
+        own.density,
+        params.target_density,
+        params.pressure_multiplier,
+    );
+    let own_near_pressure = own.near * params.near_pressure_multiplier;
+    let mut force = Pair::new(0.0, 0.0);
+
+    for j in grid.query_radius(position, radius) {
+        let offset = position - positions[j];
+        let distance = offset.magnitude();
+        if j == i || distance <= 0.0 {
+            continue; // No direction to push in
+        }
+        let direction = offset / distance; // Away from j
+        let other = densities[j];
+
+        let density_sum = own.density + other.density;
+        if density_sum > 0.0 {
+            let other_pressure = density_to_pressure(
+                other.density,
+                params.target_density,
+                params.pressure_multiplier,
+            );
+            let slope = density_kernel_slope(radius, distance);
+            force -= direction * (slope * (own_pressure + other_pressure) / density_sum);
+        }
+
+        let near_sum = own.near + other.near;
+        if near_sum > 0.0 {
+            let other_near_pressure = other.near * params.near_pressure_multiplier;
+            let slope = near_density_kernel_slope(radius, distance);
+            force -= direction * (slope * (own_near_pressure + other_near_pressure) / near_sum);
+        }
+    }
+
+    force * params.particle_mass
+}
+
+// Laplacian-style viscosity: moves dot `i`'s velocity toward its neighbors',
+// weighted by the kernel. Symmetric like the pressure, so it only takes
+// energy out of relative motion.
+pub fn viscosity_force(
+    i: usize,
+    positions: &[Pair],
+    velocities: &[Pair],
+    densities: &[Densities],
+    grid: &SpatialGrid,
+    params: &SphParams,
+) -> Pair {
+    if params.viscosity <= 0.0 {
+        return Pair::new(0.0, 0.0);
+    }
+    let radius = params.kernel_radius;
+    let position = positions[i];
+    let mut force = Pair::new(0.0, 0.0);
+
+    for j in grid.query_radius(position, radius) {
+        if j == i {
+            continue;
+        }
+        let density_sum = densities[i].density + densities[j].density;
+        if density_sum <= 0.0 {
+            continue;
+        }
+        let weight =
+            2.0 * params.particle_mass * density_kernel(radius, position.distance(positions[j]))
+                / density_sum;
+        force += (velocities[j] - velocities[i]) * weight;
+    }
+
+    force * params.viscosity
+}
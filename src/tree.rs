@@ -0,0 +1,275 @@
+use crate::bounds::BoundsArea;
+use crate::math::Pair;
+use crate::watch::FsEvent;
+use crate::{Dot, State};
+use rand::Rng;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+use tiny_skia::Color;
+
+pub const MAX_FILES: usize = 50_000;
+pub const CLUSTER_SPACING: f32 = 400.0; // Distance from the root to its subdirectories
+pub const ORBIT_RADIUS: f32 = 150.0; // Orbit of files around the root directory
+pub const BOUNDS_PADDING: f32 = 100.0; // Room around the outermost orbits
+
+// A directory; its files orbit `position`.
+#[derive(Debug, Clone)]
+pub struct Cluster {
+    pub position: Pair,
+    pub path: PathBuf,
+    pub label: String,
+    pub depth: usize,
+}
+
+impl Cluster {
+    pub fn new(position: Pair, path: PathBuf, depth: usize) -> Self {
+        let label = file_label(&path);
+        Cluster {
+            position,
+            path,
+            label,
+            depth,
+        }
+    }
+
+    // Deeper directories get tighter orbits so neighbouring clusters don't overlap
+    pub fn orbit_radius(&self) -> f32 {
+        ORBIT_RADIUS / (self.depth + 1) as f32
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct FileEntry {
+    pub path: PathBuf,
+    pub size: u64,
+    pub cluster: usize,
+}
+
+// Walks `root` without following symlinks, laying directories out as clusters
+// around their parent. Entries are sorted so a given tree always lays out the same.
+// Unreadable subdirectories are skipped; only an unreadable root is an error.
+pub fn scan(root: &Path) -> io::Result<(Vec<Cluster>, Vec<FileEntry>)> {
+    let mut clusters = vec![Cluster::new(Pair::new(0.0, 0.0), root.to_path_buf(), 0)];
+    let mut files = Vec::new();
+    let mut pending = vec![0];
+
+    while let Some(cluster) = pending.pop() {
+        if files.len() >= MAX_FILES {
+            break;
+        }
+        let read_dir = match fs::read_dir(&clusters[cluster].path) {
+            Ok(read_dir) => read_dir,
+            Err(err) if cluster == 0 => return Err(err),
+            Err(_) => continue,
+        };
+        let mut entries: Vec<_> = read_dir.filter_map(Result::ok).collect();
+        entries.sort_by_key(|entry| entry.file_name());
+
+        let mut subdirectories = Vec::new();
+        for entry in entries {
+            let Ok(metadata) = entry.path().symlink_metadata() else {
+                continue;
+            };
+            if metadata.is_dir() {
+                subdirectories.push(entry.path());
+            } else if files.len() < MAX_FILES {
+                files.push(FileEntry {
+                    path: entry.path(),
+                    size: metadata.len(),
+                    cluster,
+                });
+            }
+        }
+
+        let parent = clusters[cluster].position;
+        let depth = clusters[cluster].depth + 1;
+        let spacing = CLUSTER_SPACING / depth as f32;
+        let count = subdirectories.len();
+        for (i, path) in subdirectories.into_iter().enumerate() {
+            let angle = std::f32::consts::TAU * i as f32 / count as f32 + depth as f32;
+            let position = parent + Pair::new(angle.cos(), angle.sin()) * spacing;
+            pending.push(clusters.len());
+            clusters.push(Cluster::new(position, path, depth));
+        }
+    }
+
+    Ok((clusters, files))
+}
+
+// Replaces the dots in `state` with one per file under `root`.
+pub fn populate(state: &mut State, root: &Path) -> io::Result<()> {
+    let (clusters, files) = scan(root)?;
+    let ndots = files.len().max(1) as f32;
+
+    state.dots = files
+        .iter()
+        .map(|file| file_dot(file, &clusters[file.cluster], ndots, &mut state.rng))
+        .collect();
+    // Keep the bounds' edge rules but stretch them around every cluster, and
+    // pull the camera back far enough to show them all
+    let mut min = Pair::new(0.0, 0.0);
+    let mut max = Pair::new(0.0, 0.0);
+    for cluster in &clusters {
+        include_cluster(&mut min, &mut max, cluster);
+    }
+    state.bounds.area = BoundsArea::World { min, max };
+    let view = state.logical_size();
+    let extent = max - min;
+    state.camera.center = (min + max) / 2.0;
+    state.camera.scale = (view.x / extent.x).min(view.y / extent.y).min(1.0);
+
+    state.clusters = clusters;
+    state.speed_scale = 1.0 / ndots;
+    state.force_scale = 1.0 / ndots;
+    Ok(())
+}
+
+// Grows the rect from `min` to `max` to take in a cluster's orbit, with room
+// to spare
+fn include_cluster(min: &mut Pair, max: &mut Pair, cluster: &Cluster) {
+    let reach = cluster.orbit_radius() + BOUNDS_PADDING;
+    *min = Pair::new(
+        min.x.min(cluster.position.x - reach),
+        min.y.min(cluster.position.y - reach),
+    );
+    *max = Pair::new(
+        max.x.max(cluster.position.x + reach),
+        max.y.max(cluster.position.y + reach),
+    );
+}
+
+// A dot on a random point of its cluster's orbit, moving along it
+fn file_dot(file: &FileEntry, cluster: &Cluster, ndots: f32, rng: &mut impl Rng) -> Dot {
+    let orbit_radius = cluster.orbit_radius();
+    let speed = (orbit_radius / (ndots * 10.0)).sqrt() * 0.1;
+    let angle = rng.gen_range(0.0..std::f32::consts::TAU);
+    let position = cluster.position + Pair::new(angle.cos(), angle.sin()) * orbit_radius;
+    let velocity = Pair::new(-speed / 2.0 * angle.sin(), speed / 2.5 * angle.cos());
+
+    let mut dot = Dot::new(position, velocity, 0.0, file_color(&file.path));
+    dot.label = file_label(&file.path);
+    dot.radius_scale = size_to_radius_scale(file.size);
+    dot.cluster = file.cluster;
+    dot.path = Some(file.path.clone());
+    dot
+}
+
+// Keeps the dots in step with a change reported by the watcher
+pub fn apply_fs_event(state: &mut State, event: &FsEvent) {
+    match event {
+        FsEvent::Created { path, size } => {
+            if let Some(id) = state.dots.find_path(path) {
+                state.dots.pulse(id);
+                return;
+            }
+            if state.dots.len() >= MAX_FILES {
+                return; // Held to the same cap as scan
+            }
+            let Some(parent) = path.parent() else {
+                return;
+            };
+            let file = FileEntry {
+                path: path.clone(),
+                size: *size,
+                cluster: cluster_for(state, parent),
+            };
+            let ndots = (state.dots.len() + 1) as f32;
+            let mut dot = file_dot(&file, &state.clusters[file.cluster], ndots, &mut state.rng);
+            dot.spawn_progress = 0.0;
+            state.dots.push(dot);
+        }
+        FsEvent::Removed { path } => {
+            // Directories take everything below them along
+            state.retain_dots(|dots, id| !dots.path(id).is_some_and(|p| p.starts_with(path)));
+        }
+        FsEvent::Modified { path, size } => {
+            let dots = &mut state.dots;
+            for id in dots.ids() {
+                if dots.path(id) != Some(path.as_path()) {
+                    continue;
+                }
+                dots.pulse(id);
+                if let Some(size) = *size {
+                    dots.set_radius_scale(id, size_to_radius_scale(size));
+                }
+            }
+        }
+    }
+}
+
+// Index of the cluster for `directory`, adding clusters for it and any missing
+// parents next to their closest known ancestor
+fn cluster_for(state: &mut State, directory: &Path) -> usize {
+    if let Some(index) = state.clusters.iter().position(|c| c.path == directory) {
+        return index;
+    }
+    let Some(parent) = directory.parent().filter(|_| !state.clusters.is_empty()) else {
+        return 0;
+    };
+    let is_known_ancestor = state
+        .clusters
+        .iter()
+        .any(|c| directory.starts_with(&c.path));
+    if !is_known_ancestor {
+        return 0; // Outside the watched tree
+    }
+
+    let parent_index = cluster_for(state, parent);
+    let parent_cluster = &state.clusters[parent_index];
+    let depth = parent_cluster.depth + 1;
+    let angle = state.rng.gen_range(0.0..std::f32::consts::TAU);
+    let position = parent_cluster.position
+        + Pair::new(angle.cos(), angle.sin()) * (CLUSTER_SPACING / depth as f32);
+    let cluster = Cluster::new(position, directory.to_path_buf(), depth);
+    // Keep the new orbit inside the rect populate fitted around the others,
+    // or its dots would hit the bounds as soon as they spawn
+    if let BoundsArea::World { min, max } = &mut state.bounds.area {
+        include_cluster(min, max, &cluster);
+    }
+    state.clusters.push(cluster);
+    state.clusters.len() - 1
+}
+
+pub fn file_label(path: &Path) -> String {
+    path.file_name()
+        .unwrap_or(path.as_os_str())
+        .to_string_lossy()
+        .into_owned()
+}
+
+// Logarithmic, so a 1 GB file is a few times the size of a 1 KB one rather than
+// a million: empty files are half size, 1 KB ~1.25x, 1 MB 2x
+pub fn size_to_radius_scale(size: u64) -> f32 {
+    0.5 + ((size as f32) + 1.0).log10() / 4.0
+}
+
+// Same extension, same color. Files without one are grey.
+pub fn file_color(path: &Path) -> Color {
+    let Some(extension) = path.extension() else {
+        return Color::from_rgba8(160, 160, 160, 255);
+    };
+    let extension = extension.to_string_lossy().to_lowercase();
+
+    // FNV-1a, so colors don't change between runs or builds
+    let hash = extension.bytes().fold(0x811c9dc5u32, |hash, byte| {
+        (hash ^ byte as u32).wrapping_mul(0x01000193)
+    });
+    let hue = (hash % 360) as f32;
+    hsv_to_color(hue, 0.65, 0.9)
+}
+
+fn hsv_to_color(hue: f32, saturation: f32, value: f32) -> Color {
+    let c = value * saturation;
+    let x = c * (1.0 - ((hue / 60.0) % 2.0 - 1.0).abs());
+    let m = value - c;
+    let (r, g, b) = match hue as u32 / 60 {
+        0 => (c, x, 0.0),
+        1 => (x, c, 0.0),
+        2 => (0.0, c, x),
+        3 => (0.0, x, c),
+        4 => (x, 0.0, c),
+        _ => (c, 0.0, x),
+    };
+    Color::from_rgba(r + m, g + m, b + m, 1.0).unwrap_or(Color::WHITE)
+}
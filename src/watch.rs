@@ -0,0 +1,125 @@
+use futures_util::StreamExt;
+use inotify::{EventMask, Inotify, WatchDescriptor, WatchMask, Watches};
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+use winit::event_loop::EventLoopProxy;
+
+// A change under the watched directory, delivered to the event loop as a user
+// event. Sizes are read here, so applying one doesn't look at the disk and a
+// recorded one replays the same way.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "kind", rename_all = "lowercase")]
+pub enum FsEvent {
+    Created { path: PathBuf, size: u64 },
+    // A file, or a directory and everything under it
+    Removed { path: PathBuf },
+    // No size if the file couldn't be read
+    Modified { path: PathBuf, size: Option<u64> },
+}
+
+const WATCH_MASK: WatchMask = WatchMask::CREATE
+    .union(WatchMask::DELETE)
+    .union(WatchMask::MODIFY)
+    .union(WatchMask::MOVED_FROM)
+    .union(WatchMask::MOVED_TO)
+    .union(WatchMask::DONT_FOLLOW);
+
+// Starts watching `root` and every directory below it on the current tokio
+// runtime. Events go through `proxy` so only the event loop ever touches State.
+// The task ends when the event loop goes away.
+pub fn spawn(root: &Path, proxy: EventLoopProxy<FsEvent>) -> io::Result<()> {
+    let inotify = Inotify::init()?;
+    let mut watches = inotify.watches();
+    let mut directories = HashMap::new();
+    watch_tree(&mut watches, &mut directories, root, None)?;
+
+    let mut stream = inotify.into_event_stream([0; 4096])?;
+    tokio::spawn(async move {
+        while let Some(Ok(event)) = stream.next().await {
+            let (Some(directory), Some(name)) = (directories.get(&event.wd), event.name) else {
+                if event.mask.contains(EventMask::IGNORED) {
+                    directories.remove(&event.wd);
+                }
+                continue;
+            };
+            let path = directory.join(name);
+            let mut events = Vec::new();
+
+            if event
+                .mask
+                .intersects(EventMask::CREATE | EventMask::MOVED_TO)
+            {
+                if event.mask.contains(EventMask::ISDIR) {
+                    // New directories need their own watch; anything already
+                    // inside them was created before it existed
+                    let _ = watch_tree(
+                        &mut stream.watches(),
+                        &mut directories,
+                        &path,
+                        Some(&mut events),
+                    );
+                } else if let Ok(metadata) = path.symlink_metadata() {
+                    let size = metadata.len();
+                    events.push(FsEvent::Created { path, size });
+                }
+            } else if event
+                .mask
+                .intersects(EventMask::DELETE | EventMask::MOVED_FROM)
+            {
+                events.push(FsEvent::Removed { path });
+            } else if event.mask.contains(EventMask::MODIFY) {
+                let size = path.symlink_metadata().ok().map(|metadata| metadata.len());
+                events.push(FsEvent::Modified { path, size });
+            }
+
+            for fs_event in events {
+                if proxy.send_event(fs_event).is_err() {
+                    return; // Event loop closed
+                }
+            }
+        }
+    });
+
+    Ok(())
+}
+
+// Adds a watch for `root` and each directory under it, reporting the files
+// found along the way when `found` is given
+fn watch_tree(
+    watches: &mut Watches,
+    directories: &mut HashMap<WatchDescriptor, PathBuf>,
+    root: &Path,
+    mut found: Option<&mut Vec<FsEvent>>,
+) -> io::Result<()> {
+    let mut pending = vec![root.to_path_buf()];
+    while let Some(directory) = pending.pop() {
+        match watches.add(&directory, WATCH_MASK) {
+            Ok(wd) => {
+                directories.insert(wd, directory.clone());
+            }
+            Err(err) if directory == root => return Err(err),
+            Err(_) => continue,
+        }
+
+        let Ok(read_dir) = fs::read_dir(&directory) else {
+            continue;
+        };
+        for entry in read_dir.filter_map(Result::ok) {
+            let Ok(metadata) = entry.path().symlink_metadata() else {
+                continue;
+            };
+            if metadata.is_dir() {
+                pending.push(entry.path());
+            } else if let Some(found) = found.as_deref_mut() {
+                found.push(FsEvent::Created {
+                    path: entry.path(),
+                    size: metadata.len(),
+                });
+            }
+        }
+    }
+    Ok(())
+}
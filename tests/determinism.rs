@@ -0,0 +1,23 @@
+use gushy::{MAX_SEED, State, headless};
+
+fn run(seed: u64, steps: u64) -> u64 {
+    let mut state = State::with_seed(60, 800, 600, seed);
+    headless::step(&mut state, steps);
+    state.fingerprint()
+}
+
+#[test]
+fn same_seed_gives_identical_runs() {
+    assert_eq!(run(7, 200), run(7, 200));
+}
+
+#[test]
+fn different_seeds_give_different_runs() {
+    assert_ne!(run(7, 200), run(8, 200));
+}
+
+#[test]
+fn seeds_too_big_to_save_are_masked() {
+    let state = State::with_seed(1, 800, 600, u64::MAX);
+    assert_eq!(state.seed, MAX_SEED);
+}
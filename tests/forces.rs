@@ -0,0 +1,94 @@
+use gushy::forces::{Centripetal, Forces, Gravity, Viscosity};
+use gushy::math::Pair;
+use gushy::{State, headless};
+
+fn names(forces: &Forces) -> Vec<&str> {
+    forces.names().collect()
+}
+
+#[test]
+fn adding_a_taken_name_replaces_the_old_force() {
+    let mut forces = Forces::builtin();
+    let count = forces.slots.len();
+    let index = forces.position("centripetal").unwrap();
+    forces.set_enabled("centripetal", false);
+
+    forces.insert(0, Box::new(Centripetal { strength: 2.0 }));
+    assert_eq!(forces.slots.len(), count);
+    assert_eq!(forces.position("centripetal"), Some(0));
+    assert!(forces.is_enabled("centripetal"));
+
+    forces.move_to("centripetal", index);
+    forces.push(Gravity {
+        acceleration: Pair::new(0.0, 1.0),
+    });
+    assert_eq!(forces.slots.len(), count);
+    assert_eq!(forces.names().last(), Some("gravity"));
+    assert_eq!(
+        names(&forces).iter().filter(|&&n| n == "gravity").count(),
+        1
+    );
+}
+
+#[test]
+fn move_to_reorders_and_clamps() {
+    let mut forces = Forces::builtin();
+    assert!(forces.move_to("viscosity", 0));
+    assert_eq!(
+        names(&forces),
+        [
+            "viscosity",
+            "pressure",
+            "gravity",
+            "centripetal",
+            "center-repulsion",
+            "particle-repulsion"
+        ]
+    );
+
+    assert!(forces.move_to("pressure", 100));
+    assert_eq!(names(&forces).last(), Some(&"pressure"));
+    assert_eq!(names(&forces).len(), 6);
+}
+
+#[test]
+fn missing_names_change_nothing() {
+    let mut forces = Forces::builtin();
+    forces.set_enabled("gravity", false);
+    let before = names(&forces).join(",");
+
+    assert!(!forces.toggle("missing"));
+    assert!(!forces.move_to("missing", 0));
+    assert!(!forces.set_enabled("missing", true));
+    assert!(forces.remove("missing").is_none());
+    assert!(!forces.is_enabled("missing"));
+
+    assert_eq!(names(&forces).join(","), before);
+    assert!(!forces.is_enabled("gravity"));
+    assert!(forces.is_enabled("pressure"));
+}
+
+#[test]
+fn toggle_flips_one_force() {
+    let mut forces = Forces::builtin();
+    assert!(forces.toggle("viscosity"));
+    assert!(!forces.is_enabled("viscosity"));
+    assert_eq!(forces.slots.iter().filter(|slot| !slot.enabled).count(), 1);
+    assert!(forces.toggle("viscosity"));
+    assert!(forces.is_enabled("viscosity"));
+
+    let viscosity = forces.remove("viscosity").unwrap();
+    assert_eq!(viscosity.name(), "viscosity");
+    forces.insert(3, Box::new(Viscosity));
+    assert_eq!(names(&forces), names(&Forces::builtin()));
+}
+
+// From `gushy --headless --seed 7 --dots 30 --steps 400` before the forces
+// moved behind the Force trait; the builtin list has to keep giving it
+#[test]
+fn builtin_forces_match_the_original_update() {
+    let mut state = State::with_seed(30, 800, 600, 7);
+    assert!(state.forces.slots.iter().all(|slot| slot.enabled));
+    headless::step(&mut state, 400);
+    assert_eq!(state.fingerprint(), 0x7f988a71dcac3bca);
+}
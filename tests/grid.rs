@@ -0,0 +1,80 @@
+use gushy::grid::SpatialGrid;
+use gushy::math::Pair;
+use rand::{Rng, SeedableRng};
+use rand_chacha::ChaCha8Rng;
+
+fn random_points(rng: &mut ChaCha8Rng, count: usize, spread: f32) -> Vec<Pair> {
+    (0..count)
+        .map(|_| {
+            Pair::new(
+                rng.gen_range(-spread..spread),
+                rng.gen_range(-spread..spread),
+            )
+        })
+        .collect()
+}
+
+fn brute_force(points: &[Pair], center: Pair, radius: f32) -> Vec<usize> {
+    (0..points.len())
+        .filter(|&i| {
+            let offset = points[i] - center;
+            offset.dot(offset) <= radius * radius
+        })
+        .collect()
+}
+
+// Compares every query against checking each point, from centers inside,
+// around and well outside the points, with radii up to several times the
+// grid's size
+fn check(points: &[Pair], cell_size: f32, spread: f32, rng: &mut ChaCha8Rng) {
+    let mut grid = SpatialGrid::new(cell_size);
+    grid.rebuild(points.iter().copied());
+    for _ in 0..200 {
+        let reach = spread * 3.0;
+        let center = Pair::new(rng.gen_range(-reach..reach), rng.gen_range(-reach..reach));
+        let radius = rng.gen_range(0.0..spread * 5.0);
+
+        let mut found: Vec<usize> = grid.query_radius(center, radius).collect();
+        found.sort_unstable();
+        let expected = brute_force(points, center, radius);
+        assert_eq!(found, expected, "center {center:?}, radius {radius}");
+
+        let nearest = grid
+            .nearest(center, radius)
+            .map(|i| points[i].distance(center));
+        let closest = expected
+            .iter()
+            .map(|&i| points[i].distance(center))
+            .min_by(f32::total_cmp);
+        assert_eq!(nearest, closest, "center {center:?}, radius {radius}");
+    }
+}
+
+#[test]
+fn queries_match_brute_force() {
+    let mut rng = ChaCha8Rng::seed_from_u64(1);
+    let points = random_points(&mut rng, 500, 300.0);
+    check(&points, 20.0, 300.0, &mut rng);
+}
+
+// Far more cells than points allow, so the grid coarsens its cells
+#[test]
+fn coarsened_queries_match_brute_force() {
+    let mut rng = ChaCha8Rng::seed_from_u64(2);
+    let mut points = random_points(&mut rng, 50, 100.0);
+    points.push(Pair::new(1.0e6, -1.0e6)); // A runaway dot
+    check(&points, 1.0, 1.0e6, &mut rng);
+    check(&points, 1.0, 100.0, &mut rng);
+}
+
+#[test]
+fn empty_and_negative_queries_find_nothing() {
+    let mut grid = SpatialGrid::new(10.0);
+    grid.rebuild([]);
+    assert_eq!(grid.query_radius(Pair::new(0.0, 0.0), 100.0).count(), 0);
+    assert_eq!(grid.nearest(Pair::new(0.0, 0.0), 100.0), None);
+
+    grid.rebuild([Pair::new(0.0, 0.0)]);
+    assert_eq!(grid.query_radius(Pair::new(0.0, 0.0), -1.0).count(), 0);
+    assert_eq!(grid.nearest(Pair::new(0.0, 0.0), f32::NAN), None);
+}
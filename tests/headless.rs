@@ -0,0 +1,32 @@
+use gushy::headless;
+use gushy::scene::Scene;
+use std::path::Path;
+
+// Hash of the pixels from the run below. If a change to the simulation or
+// drawing is meant to alter the image, look at the new one from
+// `gushy --headless --scene scenes/two-rings.toml --seed 3 --size 160x120
+// --steps 60` and update this.
+const TWO_RINGS_IMAGE: u64 = 0xfe20d1eb181a2a23;
+
+fn hash(bytes: &[u8]) -> u64 {
+    let mut hash = 0xcbf29ce484222325u64;
+    for &byte in bytes {
+        hash = (hash ^ byte as u64).wrapping_mul(0x100000001b3);
+    }
+    hash
+}
+
+#[test]
+fn two_rings_renders_the_golden_image() {
+    let scene = Scene::load(Path::new("scenes/two-rings.toml")).unwrap();
+    let mut state = scene.build_state(160, 120, 3).unwrap();
+    headless::step(&mut state, 60);
+    let pixmap = headless::render(&mut state).unwrap();
+    assert_eq!((pixmap.width(), pixmap.height()), (160, 120));
+    assert_eq!(
+        hash(pixmap.data()),
+        TWO_RINGS_IMAGE,
+        "fingerprint {:016x}",
+        state.fingerprint()
+    );
+}
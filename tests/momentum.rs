@@ -0,0 +1,22 @@
+use gushy::headless;
+use gushy::integrate::Integrator;
+use gushy::scene::Scene;
+use std::path::Path;
+
+// Only the centripetal pull acts in the orbit scene, and it points at the
+// center, so every integrator should keep angular momentum to rounding
+#[test]
+fn orbit_keeps_angular_momentum() {
+    let scene = Scene::load(Path::new("scenes/orbit.toml")).unwrap();
+    for integrator in Integrator::ALL {
+        let mut state = scene.build_state(800, 600, 7).unwrap();
+        state.integrator = integrator;
+        headless::step(&mut state, 600);
+        let drift = state.momentum.relative();
+        assert!(
+            drift.abs() < 1e-6,
+            "{} drifted {drift:e}",
+            integrator.name()
+        );
+    }
+}
@@ -0,0 +1,44 @@
+use gushy::integrate::Integrator;
+use gushy::{State, headless};
+
+// Fingerprints from the serial build. The parallel build has to match them
+// exactly; if a change to the simulation moves them, update them from a
+// serial `cargo test` run.
+const SERIAL_FINGERPRINTS: [(Integrator, u64); 3] = [
+    (Integrator::SemiImplicitEuler, 0xd76b93dffeaae59c),
+    (Integrator::VelocityVerlet, 0xc107aa5cce634d49),
+    (Integrator::Rk4, 0xf3c25f55db664b82),
+];
+
+fn fingerprint(integrator: Integrator) -> u64 {
+    let mut state = State::with_seed(200, 800, 600, 7);
+    state.integrator = integrator;
+    headless::step(&mut state, 30);
+    state.fingerprint()
+}
+
+fn check_fingerprints() {
+    for (integrator, expected) in SERIAL_FINGERPRINTS {
+        assert_eq!(
+            fingerprint(integrator),
+            expected,
+            "{} gave {:016x}",
+            integrator.name(),
+            fingerprint(integrator)
+        );
+    }
+}
+
+#[cfg(feature = "parallel")]
+#[test]
+fn parallel_steps_match_serial() {
+    check_fingerprints();
+}
+
+// Keeps the stored values current, so the parallel test compares against
+// what the serial build really gives
+#[cfg(not(feature = "parallel"))]
+#[test]
+fn serial_fingerprints_are_current() {
+    check_fingerprints();
+}
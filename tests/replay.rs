@@ -0,0 +1,89 @@
+use gushy::input::{self, InputEvent, Key};
+use gushy::replay::{Recording, Replay};
+use gushy::watch::FsEvent;
+use gushy::{State, headless, tree};
+use std::fs;
+use std::path::PathBuf;
+
+fn press(state: &mut State, recording: &mut Recording, key: Key) {
+    let event = InputEvent::Key { key };
+    recording.record(state, event);
+    input::apply(state, &event);
+}
+
+#[test]
+fn replays_leave_files_alone_and_quickload_what_was_recorded() {
+    let dir = std::env::temp_dir().join(format!("gushy-replay-{}", std::process::id()));
+    fs::create_dir_all(&dir).unwrap();
+    let (quick_save, session) = (dir.join("quicksave.toml"), dir.join("session.toml"));
+
+    let mut state = State::with_seed(30, 800, 600, 7);
+    state.quick_save = quick_save.clone();
+    let mut recording = Recording::new(&state);
+    headless::step(&mut state, 20);
+    press(&mut state, &mut recording, Key::F5);
+    headless::step(&mut state, 20);
+    press(&mut state, &mut recording, Key::F9);
+    headless::step(&mut state, 20);
+    recording.finish(&state);
+
+    // Whatever is on disk now shouldn't matter to the replay, nor be touched
+    fs::write(&quick_save, "not a snapshot").unwrap();
+    recording.save(&session).unwrap();
+    let mut replay = Replay::new(Recording::load(&session).unwrap());
+    let mut replayed = State::with_seed(1, 800, 600, 1);
+    replayed.quick_save = quick_save.clone();
+    replay.start(&mut replayed);
+    replay.run(&mut replayed);
+
+    let on_disk = fs::read_to_string(&quick_save).unwrap();
+    fs::remove_dir_all(&dir).unwrap();
+    assert_eq!(on_disk, "not a snapshot");
+    assert_eq!(replayed.time_info.step_count, state.time_info.step_count);
+    assert_eq!(replayed.fingerprint(), state.fingerprint());
+}
+
+#[test]
+fn watcher_changes_replay_as_recorded() {
+    let mut state = State::with_seed(30, 800, 600, 7);
+    let mut recording = Recording::new(&state);
+    let root = PathBuf::from("/watched");
+    let events = [
+        FsEvent::Created {
+            path: root.join("a.txt"),
+            size: 10,
+        },
+        FsEvent::Created {
+            path: root.join("sub/b.rs"),
+            size: 4096,
+        },
+        FsEvent::Modified {
+            path: root.join("a.txt"),
+            size: Some(1 << 20),
+        },
+        FsEvent::Modified {
+            path: root.join("unreadable"),
+            size: None,
+        },
+        FsEvent::Removed {
+            path: root.join("sub"),
+        },
+    ];
+    for event in events {
+        headless::step(&mut state, 10);
+        recording.record(&state, event.clone());
+        tree::apply_fs_event(&mut state, &event);
+    }
+    headless::step(&mut state, 10);
+    recording.finish(&state);
+
+    let text = toml::to_string(&recording).unwrap();
+    let mut replay = Replay::new(toml::from_str(&text).unwrap());
+    let mut replayed = State::with_seed(1, 800, 600, 1);
+    replay.start(&mut replayed);
+    replay.run(&mut replayed);
+
+    assert_eq!(replayed.dots.len(), state.dots.len());
+    assert_eq!(replayed.clusters.len(), state.clusters.len());
+    assert_eq!(replayed.fingerprint(), state.fingerprint());
+}
@@ -0,0 +1,50 @@
+use gushy::bounds::{BoundsArea, EdgeBehavior};
+use gushy::math::Pair;
+use gushy::scene::{Scene, SceneError};
+
+fn parse(text: &str) -> Result<Scene, SceneError> {
+    let scene: Scene = toml::from_str(text).unwrap();
+    scene.validate().map(|()| scene)
+}
+
+#[test]
+fn bounds_set_each_edge_and_a_world_rect() {
+    let scene = parse(
+        r#"
+        [sim]
+        edges = "absorb"
+
+        [sim.bounds]
+        left = "wrap"
+        top = "open"
+        world = { x = -100.0, y = -50.0, width = 200.0, height = 100.0 }
+        "#,
+    )
+    .unwrap();
+    let state = scene.build_state(800, 600, 7).unwrap();
+    let bounds = state.bounds;
+    assert_eq!(bounds.left, EdgeBehavior::Wrap);
+    assert_eq!(bounds.right, EdgeBehavior::Absorb);
+    assert_eq!(bounds.top, EdgeBehavior::Open);
+    assert_eq!(bounds.bottom, EdgeBehavior::Absorb);
+    assert_eq!(
+        bounds.area,
+        BoundsArea::World {
+            min: Pair::new(-100.0, -50.0),
+            max: Pair::new(100.0, 50.0),
+        }
+    );
+}
+
+#[test]
+fn empty_world_rects_are_refused() {
+    let error = parse(
+        r#"
+        [sim.bounds]
+        world = { x = 0.0, y = 0.0, width = 0.0, height = 100.0 }
+        "#,
+    )
+    .map(|_| ())
+    .unwrap_err();
+    assert!(matches!(error, SceneError::Invalid { field, .. } if field == "sim.bounds.world"));
+}
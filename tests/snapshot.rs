@@ -0,0 +1,44 @@
+use gushy::file::FileError;
+use gushy::snapshot::{self, SNAPSHOT_VERSION, Snapshot};
+use gushy::{State, headless};
+
+#[test]
+fn save_then_load_carries_on_bit_for_bit() {
+    let mut original = State::with_seed(40, 800, 600, 7);
+    headless::step(&mut original, 50);
+    let selected = original.dots.id(3).unwrap();
+    original.selection.insert(selected);
+    original.forces.set_enabled("gravity", false);
+
+    let path = std::env::temp_dir().join(format!("gushy-test-{}.toml", std::process::id()));
+    snapshot::save(&original, &path).unwrap();
+    let mut restored = State::with_seed(5, 800, 600, 1);
+    let loaded = snapshot::load(&mut restored, &path);
+    std::fs::remove_file(&path).unwrap();
+    loaded.unwrap();
+
+    assert_eq!(restored.seed, original.seed);
+    assert_eq!(restored.fingerprint(), original.fingerprint());
+    // Handles from the saved state don't reach into the restored one
+    assert!(!restored.dots.contains(selected));
+    assert!(restored.selection.contains(restored.dots.id(3).unwrap()));
+    assert!(!restored.forces.is_enabled("gravity"));
+
+    headless::step(&mut original, 50);
+    headless::step(&mut restored, 50);
+    assert_eq!(restored.fingerprint(), original.fingerprint());
+}
+
+#[test]
+fn newer_versions_are_refused() {
+    let state = State::with_seed(5, 800, 600, 7);
+    let text = Snapshot::capture(&state).to_toml().unwrap().replacen(
+        &format!("version = {SNAPSHOT_VERSION}"),
+        &format!("version = {}", SNAPSHOT_VERSION + 1),
+        1,
+    );
+    assert!(matches!(
+        Snapshot::from_toml(&text),
+        Err(FileError::Version { version, .. }) if version == SNAPSHOT_VERSION + 1
+    ));
+}
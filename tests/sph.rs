@@ -0,0 +1,69 @@
+use gushy::SMOOTHING_RADIUS;
+use gushy::grid::SpatialGrid;
+use gushy::math::Pair;
+use gushy::sph::{
+    DEFAULT_NEAR_PRESSURE_MULTIPLIER, DEFAULT_PARTICLE_MASS, DEFAULT_VISCOSITY, Densities,
+    SphParams, pressure_force,
+};
+use rand::{Rng, SeedableRng};
+use rand_chacha::ChaCha8Rng;
+
+const PARAMS: SphParams = SphParams {
+    kernel_radius: SMOOTHING_RADIUS,
+    particle_mass: DEFAULT_PARTICLE_MASS,
+    target_density: 0.05,
+    pressure_multiplier: 10.0,
+    near_pressure_multiplier: DEFAULT_NEAR_PRESSURE_MULTIPLIER,
+    viscosity: DEFAULT_VISCOSITY,
+};
+
+// Two dots closer than the kernel radius, with whatever densities
+fn random_pair(rng: &mut ChaCha8Rng) -> ([Pair; 2], [Densities; 2]) {
+    let a = Pair::new(rng.gen_range(-50.0..50.0), rng.gen_range(-50.0..50.0));
+    let angle = rng.gen_range(0.0..std::f32::consts::TAU);
+    let distance = rng.gen_range(0.5..PARAMS.kernel_radius * 0.99);
+    let b = a + Pair::new(angle.cos(), angle.sin()) * distance;
+    let mut densities = || Densities {
+        density: rng.gen_range(0.0..0.2),
+        near: rng.gen_range(0.0001..0.01),
+    };
+    ([a, b], [densities(), densities()])
+}
+
+fn forces(positions: &[Pair; 2], densities: &[Densities; 2], params: &SphParams) -> [Pair; 2] {
+    let mut grid = SpatialGrid::new(params.kernel_radius);
+    grid.rebuild(positions.iter().copied());
+    [0, 1].map(|i| pressure_force(i, positions, densities, &grid, params))
+}
+
+#[test]
+fn pair_forces_are_equal_and_opposite() {
+    let mut rng = ChaCha8Rng::seed_from_u64(1);
+    for _ in 0..1000 {
+        let (positions, densities) = random_pair(&mut rng);
+        let [on_a, on_b] = forces(&positions, &densities, &PARAMS);
+        let scale = on_a.magnitude().max(on_b.magnitude());
+        assert!(
+            (on_a + on_b).magnitude() <= scale * 1e-5,
+            "{on_a:?} and {on_b:?} for {positions:?}, {densities:?}"
+        );
+    }
+}
+
+#[test]
+fn near_pressure_pushes_apart() {
+    let params = SphParams {
+        pressure_multiplier: 0.0, // Leaves only the near pressure
+        ..PARAMS
+    };
+    let mut rng = ChaCha8Rng::seed_from_u64(2);
+    for _ in 0..1000 {
+        let (positions, densities) = random_pair(&mut rng);
+        let [on_a, _] = forces(&positions, &densities, &params);
+        let away = positions[0] - positions[1];
+        assert!(
+            on_a.dot(away) > 0.0,
+            "{on_a:?} for {positions:?}, {densities:?}"
+        );
+    }
+}
@@ -0,0 +1,37 @@
+use gushy::State;
+use gushy::input::{self, InputEvent, throw_velocity};
+use gushy::math::Pair;
+
+// A 1000 Hz mouse reports many times within one 60 Hz step
+#[test]
+fn samples_within_one_step_still_throw() {
+    let mut state = State::with_seed(10, 800, 600, 7);
+    state.mouse_info.mouse_down = true;
+    for i in 0..40 {
+        state.time_info.input_time = 1.0 + i as f64 * 0.001;
+        let cursor = InputEvent::Cursor {
+            x: 400.0 + i as f32 * 0.5,
+            y: 300.0,
+        };
+        input::apply(&mut state, &cursor);
+    }
+    assert_eq!(state.time_info.step_count, 0);
+
+    let start = state.screen_to_world(Pair::new(400.0, 300.0));
+    let end = state.screen_to_world(Pair::new(400.0 + 39.0 * 0.5, 300.0));
+    let expected = (end - start) / 0.039;
+    let throw = throw_velocity(&mut state);
+    assert!((throw - expected).magnitude() < expected.magnitude() * 1e-3);
+}
+
+#[test]
+fn a_cursor_held_still_throws_nothing() {
+    let mut state = State::with_seed(10, 800, 600, 7);
+    state.mouse_info.mouse_down = true;
+    for (time, x) in [(1.0, 400.0), (1.01, 420.0)] {
+        state.time_info.input_time = time;
+        input::apply(&mut state, &InputEvent::Cursor { x, y: 300.0 });
+    }
+    state.time_info.input_time = 2.0;
+    assert_eq!(throw_velocity(&mut state), Pair::new(0.0, 0.0));
+}
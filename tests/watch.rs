@@ -0,0 +1,41 @@
+use gushy::State;
+use gushy::bounds::BoundsArea;
+use gushy::tree::{self, MAX_FILES};
+use gushy::watch::FsEvent;
+use std::fs;
+
+#[test]
+fn created_files_stop_at_the_cap() {
+    let mut state = State::with_seed(MAX_FILES, 800, 600, 7);
+    tree::apply_fs_event(
+        &mut state,
+        &FsEvent::Created {
+            path: "/watched/one-too-many".into(),
+            size: 1,
+        },
+    );
+    assert_eq!(state.dots.len(), MAX_FILES);
+}
+
+#[test]
+fn new_directories_grow_the_world_bounds() {
+    let root = std::env::temp_dir().join(format!("gushy-watch-{}", std::process::id()));
+    fs::create_dir_all(&root).unwrap();
+    fs::write(root.join("file.txt"), "x").unwrap();
+    let state = State::from_path(&root, 800, 600);
+    fs::remove_dir_all(&root).unwrap();
+    let mut state = state.unwrap();
+
+    // Deep enough that every new cluster lands well away from the first
+    let path = root.join("a/b/c/d/file.txt");
+    tree::apply_fs_event(&mut state, &FsEvent::Created { path, size: 1 });
+    let BoundsArea::World { min, max } = state.bounds.area else {
+        panic!("populate sets world bounds");
+    };
+    for cluster in &state.clusters {
+        let reach = cluster.orbit_radius();
+        let position = cluster.position;
+        assert!(position.x - reach >= min.x && position.x + reach <= max.x);
+        assert!(position.y - reach >= min.y && position.y + reach <= max.y);
+    }
+}